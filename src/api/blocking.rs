@@ -0,0 +1,126 @@
+// Purpose: Synchronous wrapper around the async HFOPT client
+
+use crate::config;
+use crate::error::{FlotillaError, Result};
+use crate::session;
+use super::{Collection, IdType, NewCollection, Ship, UserData};
+use tokio::runtime::Runtime;
+
+/// Blocking flavor of [`super::Flotilla`], driving each call to completion on its own runtime.
+///
+/// Must not be used from within an async context.
+#[derive(Debug)]
+pub struct Flotilla {
+    inner: super::Flotilla,
+    rt: Runtime,
+}
+
+fn runtime() -> Runtime {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("Application Error: Could not start the async runtime. Please file a bug!")
+}
+
+pub fn login(config: &config::Config) -> Result<session::Session>
+{
+    runtime().block_on(super::login(config))
+}
+
+impl Flotilla {
+    pub fn new(config: config::Config, session: session::Session) -> Self {
+        Self {
+            inner: super::Flotilla::new(config, session),
+            rt: runtime(),
+        }
+    }
+
+    /// The async client this wraps
+    pub fn inner(&self) -> &super::Flotilla {
+        &self.inner
+    }
+
+    pub fn config(&self) -> &config::Config {
+        &self.inner.config
+    }
+
+    /// A copy of the current session, including any tokens refreshed since it was loaded
+    pub fn session(&self) -> session::Session {
+        self.rt.block_on(self.inner.session())
+    }
+
+    pub fn get_json_by_id(&self, id: &String) -> Result<serde_json::Value> {
+        self.rt.block_on(self.inner.get_json_by_id(id))
+    }
+
+    pub fn get_user_data(&self) -> Result<UserData> {
+        self.rt.block_on(self.inner.get_user_data())
+    }
+
+    pub fn resolve_ids(&self, queries: &[String], kind: Option<IdType>) -> Result<Vec<String>> {
+        self.rt.block_on(self.inner.resolve_ids(queries, kind))
+    }
+
+    /// The full id meant by `query`, see [`super::Flotilla::resolve_ids`]
+    pub fn resolve_id(&self, query: &str, kind: Option<IdType>) -> Result<String> {
+        self.resolve_ids(&[query.to_string()], kind)?
+            .pop()
+            .ok_or_else(|| FlotillaError::NotFound(query.to_string()))
+    }
+
+    pub fn set_collection(&self, collection: Collection) -> Result<()> {
+        self.rt.block_on(self.inner.set_collection(collection))
+    }
+
+    pub fn set_by_id(&self, id: &str, json: serde_json::Value) -> Result<()> {
+        self.rt.block_on(self.inner.set_by_id(id, json))
+    }
+
+    pub fn set_ship(&self, ship: Ship) -> Result<()> {
+        self.rt.block_on(self.inner.set_ship(ship))
+    }
+
+    pub fn get_collection(&self, id: &String) -> Result<Collection> {
+        self.rt.block_on(self.inner.get_collection(id))
+    }
+
+    pub fn get_ship(&self, id: &String) -> Result<Ship> {
+        self.rt.block_on(self.inner.get_ship(id))
+    }
+
+    pub fn get_current_collection(&self, id: &String) -> Result<Collection> {
+        self.rt.block_on(self.inner.get_current_collection(id))
+    }
+
+    pub fn get_current_ship(&self, id: &String) -> Result<Ship> {
+        self.rt.block_on(self.inner.get_current_ship(id))
+    }
+
+    pub fn upload_ship(&self, file_name: &str, contents: String) -> Result<Ship> {
+        self.rt.block_on(self.inner.upload_ship(file_name, contents))
+    }
+
+    pub fn update_ship_file(&self, id: &String, file_name: &str, contents: String) -> Result<Ship> {
+        self.rt.block_on(self.inner.update_ship_file(id, file_name, contents))
+    }
+
+    pub fn create_collection(&self, collection: &NewCollection) -> Result<Collection> {
+        self.rt.block_on(self.inner.create_collection(collection))
+    }
+
+    pub fn delete_by_id(&self, id: &String) -> Result<()> {
+        self.rt.block_on(self.inner.delete_by_id(id))
+    }
+
+    pub fn delete_collection(&self, id: &String) -> Result<()> {
+        self.rt.block_on(self.inner.delete_collection(id))
+    }
+
+    pub fn delete_ship(&self, id: &String) -> Result<()> {
+        self.rt.block_on(self.inner.delete_ship(id))
+    }
+
+    pub fn get_public_collection(&self, id: &String) -> Result<Collection> {
+        self.rt.block_on(self.inner.get_public_collection(id))
+    }
+}
@@ -0,0 +1,555 @@
+use crate::cache;
+use crate::config;
+use crate::error::{FlotillaError, Result};
+use crate::session;
+use chrono::Utc;
+use reqwest::header::{HeaderMap, CACHE_CONTROL, ETAG, IF_NONE_MATCH};
+use reqwest::{Client, Method, Request, RequestBuilder, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use serde_json::json;
+use std::io::IsTerminal;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::Mutex;
+use tracing::{debug, info, trace, warn};
+
+pub mod blocking;
+mod redact;
+pub mod retry;
+
+pub use crate::models::{UserData, Ship, Collection, NewCollection, IdType, get_id_type, is_full_id, resolve_id};
+
+/// Async client for the HFOPT API.
+///
+/// Owns one pooled `reqwest::Client` and one session, so clones are cheap and share both
+/// connections and tokens. Use [`blocking::Flotilla`] from synchronous code.
+#[derive(Debug, Clone)]
+pub struct Flotilla {
+    pub config: config::Config,
+    session: Arc<Mutex<session::Session>>,
+    client: Client,
+    on_retry: Option<retry::Hook>,
+}
+
+pub async fn login(config: &config::Config) -> Result<session::Session>
+{
+    Flotilla::new(config.clone(), session::Session::new()).login().await
+}
+
+/// Reads a token out of a stored value, which may still carry the quotes of its JSON form
+fn stored_token(value: &str) -> Option<String> {
+    let token = value.trim_matches('"');
+    match token {
+        "" | "null" => None,
+        _ => Some(token.to_string()),
+    }
+}
+
+/// How long a response may be reused according to its Cache-Control header, and whether it may be stored at all
+fn freshness(headers: &HeaderMap) -> (Option<i64>, bool) {
+    let control = headers.get(CACHE_CONTROL).and_then(|v| v.to_str().ok()).unwrap_or("");
+    let mut max_age = None;
+    let mut store = true;
+    for directive in control.split(',').map(|d| d.trim().to_ascii_lowercase())
+    {
+        match directive.split_once('=')
+        {
+            Some(("max-age", seconds)) => max_age = seconds.parse().ok(),
+            _ if directive == "no-store" => store = false,
+            _ if directive == "no-cache" => max_age = Some(0),
+            _ => {},
+        }
+    }
+    (max_age, store)
+}
+
+/// Whether the server answered but would not renew the session, as opposed to not being reachable at all
+fn is_rejection(e: &FlotillaError) -> bool {
+    !matches!(e, FlotillaError::Network(_))
+}
+
+impl Flotilla {
+    pub fn new(config: config::Config, session: session::Session) -> Self {
+        Self {
+            config,
+            session: Arc::new(Mutex::new(session)),
+            client: Client::new(),
+            on_retry: None,
+        }
+    }
+
+    /// Calls `hook` with the attempt number and the number allowed before each retry, e.g. to
+    /// show it on a progress bar. Applies to this clone only
+    pub fn on_retry(mut self, hook: impl Fn(u32, u32) + Send + Sync + 'static) -> Self {
+        self.on_retry = Some(retry::Hook(Arc::new(hook)));
+        self
+    }
+
+    /// The shared HTTP client, for requests which must not carry our credentials
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// A copy of the current session, including any tokens refreshed since it was loaded
+    pub async fn session(&self) -> session::Session {
+        self.session.lock().await.clone()
+    }
+
+    /// Builds a full API url from a path such as `/shipyard/ship/{id}`
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.config.endpoint, path)
+    }
+
+    /// Starts an authenticated request against any url, renewing the session first if it has expired
+    pub async fn request(&self, method: Method, url: &str) -> Result<RequestBuilder> {
+        if self.config.offline
+        {
+            return Err(FlotillaError::Network(format!("Not sending {} {} while offline", method, url)));
+        }
+        if self.session.lock().await.expired()
+        {
+            self.renew().await?;
+        }
+        let session = self.session.lock().await;
+        Ok(self.client
+            .request(method, url)
+            .header("Authorization", format!("Bearer {}", session.id_token.replace('"', ""))))
+    }
+
+    async fn json_request(&self, method: Method, path: &str, body: &serde_json::Value) -> Result<RequestBuilder> {
+        Ok(self.request(method, &self.url(path)).await?
+            .header("Content-Type", "application/json")
+            .body(body.to_string()))
+    }
+
+    /// Sends a request once, turning any non-success status into the matching error. A 304 Not
+    /// Modified is passed through, as only conditional requests get one.
+    ///
+    /// Each exchange is logged at debug level, and its (redacted) headers at trace level.
+    pub async fn send(req: RequestBuilder) -> Result<Response> {
+        let (client, req) = req.build_split();
+        Self::check(Self::execute(&client, req?).await?).await
+    }
+
+    /// Like [`Flotilla::send`], but retries idempotent requests which fail on the way or with a
+    /// transient status (429, 502, 503, ...), as often as the configured `retries` allow
+    pub async fn send_retrying(&self, req: RequestBuilder) -> Result<Response> {
+        let (client, req) = req.build_split();
+        let mut req = req?;
+        let policy = retry::Policy::from_config(&self.config);
+        let retryable = policy.allows(req.method());
+        let mut attempt = 1;
+        loop
+        {
+            // Requests with streamed bodies cannot be copied, and so are only tried once
+            let next = match retryable {
+                true => req.try_clone(),
+                false => None,
+            };
+            let method = req.method().clone();
+            let url = req.url().clone();
+            let outcome = Self::execute(&client, req).await;
+            let delay = match (&outcome, &next) {
+                (_, None) => None,
+                (Ok(res), Some(_)) if retry::is_transient(res.status()) => policy.delay(attempt, retry::retry_after(res.headers())),
+                (Err(e), Some(_)) if !e.is_builder() => policy.delay(attempt, None),
+                _ => None,
+            };
+            match (delay, next)
+            {
+                (Some(delay), Some(next)) => {
+                    let reason = match &outcome {
+                        Ok(res) => res.status().to_string(),
+                        Err(e) => redact::error(e, url.as_str()),
+                    };
+                    debug!("{} {} failed ({}), retrying in {} ms (attempt {}/{})", method, redact::url(url.as_str()), reason, delay.as_millis(), attempt + 1, policy.attempts);
+                    if let Some(hook) = &self.on_retry
+                    {
+                        (hook.0)(attempt + 1, policy.attempts);
+                    }
+                    tokio::time::sleep(delay).await;
+                    req = next;
+                    attempt += 1;
+                },
+                _ => return Self::check(outcome?).await,
+            }
+        }
+    }
+
+    /// Sends one request, logging the exchange
+    async fn execute(client: &Client, req: Request) -> std::result::Result<Response, reqwest::Error> {
+        let method = req.method().clone();
+        let full_url = req.url().to_string();
+        let url = redact::url(&full_url);
+        trace!("{} {} request headers: {}", method, url, redact::headers(req.headers()));
+
+        let start = Instant::now();
+        let res = client.execute(req).await.inspect_err(|e| {
+            debug!("{} {} failed after {} ms: {}", method, url, start.elapsed().as_millis(), redact::error(e, &full_url));
+        })?;
+        debug!("{} {} -> {} ({} ms)", method, url, res.status(), start.elapsed().as_millis());
+        trace!("{} {} response headers: {}", method, url, redact::headers(res.headers()));
+        Ok(res)
+    }
+
+    async fn check(res: Response) -> Result<Response> {
+        let status = res.status();
+        if status.is_success() || status == StatusCode::NOT_MODIFIED {
+            return Ok(res);
+        }
+        let path = res.url().path().to_string();
+        let body = Self::text(res).await.unwrap_or_default();
+        Err(match status.as_u16() {
+            401 => FlotillaError::AuthExpired,
+            404 => FlotillaError::NotFound(path),
+            code => FlotillaError::Http { status: code, body },
+        })
+    }
+
+    /// Reads a whole response body, logging it (redacted) at trace level
+    async fn text(res: Response) -> Result<String> {
+        let url = redact::url(res.url().as_str());
+        let txt = res.text().await?;
+        trace!("{} response body: {}", url, redact::body(&txt));
+        Ok(txt)
+    }
+
+    async fn fetch<T: DeserializeOwned>(&self, req: RequestBuilder) -> Result<T> {
+        let txt = Self::text(self.send_retrying(req).await?).await?;
+        serde_json::from_str(&txt).map_err(|e| FlotillaError::parse(e, &txt))
+    }
+
+    /// GETs a url, answering from the metadata cache while the cached body is fresh, and always
+    /// when offline. Stale bodies are revalidated with their ETag.
+    ///
+    /// `anonymous` requests carry no credentials, and share their cache entries between users.
+    pub async fn get_cached(&self, url: &str, anonymous: bool) -> Result<String> {
+        self.lookup(url, anonymous, false).await
+    }
+
+    /// Like [`Flotilla::get_cached`], but `revalidate` asks the server even when the cached body
+    /// is fresh or we are offline, so that nothing read before a write is out of date
+    async fn lookup(&self, url: &str, anonymous: bool, revalidate: bool) -> Result<String> {
+        let endpoint = &self.config.endpoint;
+        let path = url.strip_prefix(endpoint.as_str()).unwrap_or(url);
+        let user = match anonymous {
+            true => "",
+            false => self.config.username.as_str(),
+        };
+        let cached = cache::load(endpoint, user, path);
+        if self.config.offline && !revalidate
+        {
+            return cached
+                .map(|entry| entry.body)
+                .ok_or_else(|| FlotillaError::NotFound(format!("{} in the offline cache", path)));
+        }
+        if let Some(entry) = cached.as_ref().filter(|e| !revalidate && e.is_fresh(self.config.cache_ttl))
+        {
+            debug!("GET {} answered from the cache", redact::url(url));
+            return Ok(entry.body.clone());
+        }
+
+        let mut req = match anonymous {
+            true => self.client.get(url),
+            false => self.request(Method::GET, url).await?,
+        };
+        if let Some(etag) = cached.as_ref().and_then(|e| e.etag.as_ref())
+        {
+            req = req.header(IF_NONE_MATCH, etag);
+        }
+        let res = self.send_retrying(req).await?;
+        let (max_age, storable) = freshness(res.headers());
+        let etag = res.headers().get(ETAG).and_then(|v| v.to_str().ok()).map(str::to_string);
+        let entry = match (res.status(), cached) {
+            (StatusCode::NOT_MODIFIED, Some(previous)) => cache::Entry {
+                max_age: max_age.or(previous.max_age),
+                etag: etag.or(previous.etag),
+                ..cache::Entry::new(endpoint, user, path, previous.body)
+            },
+            _ => cache::Entry {
+                max_age,
+                etag,
+                ..cache::Entry::new(endpoint, user, path, Self::text(res).await?)
+            },
+        };
+        if storable
+        {
+            if let Err(e) = cache::store(&entry)
+            {
+                warn!("Could not cache {}: {}", path, e);
+            }
+        }
+        Ok(entry.body)
+    }
+
+    async fn fetch_cached<T: DeserializeOwned>(&self, url: &str, anonymous: bool) -> Result<T> {
+        let txt = self.get_cached(url, anonymous).await?;
+        serde_json::from_str(&txt).map_err(|e| FlotillaError::parse(e, &txt))
+    }
+
+    async fn fetch_current<T: DeserializeOwned>(&self, url: &str) -> Result<T> {
+        let txt = self.lookup(url, false, true).await?;
+        serde_json::from_str(&txt).map_err(|e| FlotillaError::parse(e, &txt))
+    }
+
+    /// Forgets cached metadata which a change to `id` (or to our list of objects) makes stale
+    fn invalidate(&self, id: Option<&str>) {
+        cache::invalidate(&self.config.endpoint, id);
+    }
+
+    /// Posts credentials to an authentication endpoint and builds a session from the answer.
+    ///
+    /// Refreshes do not hand out a new refresh token, so the previous one is kept in that case.
+    async fn authenticate(&self, path: &str, body: serde_json::Value, previous: &session::Session) -> Result<session::Session> {
+        let req = self.client
+            .post(self.url(path))
+            .header("Content-Type", "application/json")
+            .body(body.to_string());
+        let txt = Self::text(Self::send(req).await?).await?;
+
+        let json: serde_json::Value = serde_json::from_str(&txt).map_err(|e| FlotillaError::parse(e, &txt))?;
+        let result = &json["AuthenticationResult"];
+        if !result["IdToken"].is_string() {
+            return Err(FlotillaError::parse("no IdToken in authentication result", &txt));
+        }
+        let session = session::Session {
+            id_token: result["IdToken"].to_string(),
+            user_id: self.config.username.clone(),
+            refresh_token: match result["RefreshToken"].is_string() {
+                true => result["RefreshToken"].to_string(),
+                false => previous.refresh_token.clone(),
+            },
+            expiration_unix: result["ExpiresIn"].as_i64().unwrap_or(0) + Utc::now().timestamp(),
+            endpoint: self.config.endpoint.clone(),
+        };
+        session.save_to_default()?;
+        Ok(session)
+    }
+
+    async fn password_login(&self, previous: &session::Session, password: String) -> Result<session::Session> {
+        let json_body = json!({
+            "email": self.config.username,
+            "password": password,
+        });
+        self.authenticate("/user/quick_login", json_body, previous).await
+    }
+
+    async fn refresh(&self, previous: &session::Session, refresh_token: String) -> Result<session::Session> {
+        let json_body = json!({
+            "email": self.config.username,
+            "refreshToken": refresh_token,
+        });
+        self.authenticate("/user/refresh", json_body, previous).await
+    }
+
+    /// The password to log in with, without holding the session, as it may have to ask for the passphrase
+    fn login_password(&self) -> Result<String> {
+        if self.config.username.is_empty()
+        {
+            return Err(FlotillaError::AuthExpired);
+        }
+        if self.config.password.is_empty() && std::env::var_os("FLOTILLA_PASSPHRASE").is_none() && !std::io::stdin().is_terminal()
+        {
+            return Err(FlotillaError::AuthExpired);
+        }
+        self.config.resolve_password()?.ok_or(FlotillaError::AuthExpired)
+    }
+
+    /// Exchanges the refresh token for a new session, falling back to the configured password
+    /// when the server will not refresh it
+    async fn renew(&self) -> Result<()> {
+        {
+            let mut session = self.session.lock().await;
+            if !session.expired()
+            {
+                return Ok(());
+            }
+            if let Some(token) = stored_token(&session.refresh_token)
+            {
+                match self.refresh(&session, token).await
+                {
+                    Err(e) if is_rejection(&e) => debug!("Could not refresh the session: {}", e),
+                    result => {
+                        *session = result?;
+                        return Ok(());
+                    },
+                }
+            }
+        }
+        let password = self.login_password()?;
+        let mut session = self.session.lock().await;
+        if session.expired()
+        {
+            info!("Session expired, logging in");
+            *session = self.password_login(&session, password).await?;
+        }
+        Ok(())
+    }
+
+    /// Logs in with the configured username and (stored) password unless the stored session is still valid
+    pub async fn login(&self) -> Result<session::Session>
+    {
+        let stored = session::Session::load_for(&self.config);
+        if !stored.expired()
+        {
+            *self.session.lock().await = stored.clone();
+            return Ok(stored);
+        }
+        let password = self.config.resolve_password()?.ok_or(FlotillaError::AuthExpired)?;
+        let mut session = self.session.lock().await;
+        info!("Session expired, logging in");
+        *session = self.password_login(&stored, password).await?;
+        Ok(session.clone())
+    }
+
+    /// A ship or collection as the server has it now, to be changed and written back
+    pub async fn get_json_by_id(&self, id: &String)  -> Result<serde_json::Value>{
+        match get_id_type(id)?
+        {
+            IdType::Collection => {
+                self.get_current_collection(id).await.map(|c| json!(c))
+            },
+            IdType::Ship => {
+                self.get_current_ship(id).await.map(|s| json!(s))
+            }
+        }
+    }
+
+    pub async fn get_user_data(&self) -> Result<UserData>
+    {
+        self.fetch_cached(&self.url("/user"), false).await
+    }
+
+    /// The full ids meant by each of `queries`, which may also be short ids, unique prefixes or
+    /// exact names. Only looks up our ships and collections if one of them is not a full id
+    pub async fn resolve_ids(&self, queries: &[String], kind: Option<IdType>) -> Result<Vec<String>>
+    {
+        if queries.iter().all(|q| is_full_id(q) && kind.is_none_or(|k| get_id_type(q).ok() == Some(k)))
+        {
+            return Ok(queries.to_vec());
+        }
+        let user_data = self.get_user_data().await.map_err(|e| e.context("Could not look up ids"))?;
+        let mut ids = Vec::new();
+        let mut errors = Vec::new();
+        for query in queries.iter()
+        {
+            match is_full_id(query) && kind.is_none_or(|k| get_id_type(query).ok() == Some(k))
+            {
+                true => ids.push(query.clone()),
+                false => match resolve_id(&user_data, query, kind) {
+                    Ok((id, _)) => ids.push(id),
+                    Err(e) => errors.push(e),
+                },
+            }
+        }
+        FlotillaError::from_many(errors)?;
+        Ok(ids)
+    }
+
+    pub async fn set_collection(&self, collection: Collection) -> Result<()>
+    {
+        let path = format!("/shipyard/collection/{}", collection.id);
+        self.send_retrying(self.json_request(Method::PUT, &path, &json!(collection)).await?).await?;
+        self.invalidate(Some(&collection.id));
+        Ok(())
+    }
+
+    pub async fn set_by_id(&self, id: &str, json: serde_json::Value) -> Result<()>
+    {
+        match get_id_type(id)?
+        {
+            IdType::Collection => self.set_collection( serde_json::from_value(json.clone()).map_err(|e| FlotillaError::parse(e, &json.to_string()))?).await,
+            IdType::Ship => self.set_ship( serde_json::from_value(json.clone()).map_err(|e| FlotillaError::parse(e, &json.to_string()))?).await,
+        }
+    }
+
+    pub async fn set_ship(&self, ship: Ship) -> Result<()>
+    {
+        let path = format!("/shipyard/ship/{}", ship.id);
+        self.send_retrying(self.json_request(Method::PUT, &path, &json!(ship)).await?).await?;
+        self.invalidate(Some(&ship.id));
+        Ok(())
+    }
+
+    pub async fn get_collection(&self, id: &String) -> Result<Collection>
+    {
+        self.fetch_cached(&self.url(&format!("/shipyard/collection/{}", id)), false).await
+    }
+
+    pub async fn get_ship(&self, id: &String) -> Result<Ship>
+    {
+        self.fetch_cached(&self.url(&format!("/shipyard/ship/{}", id)), false).await
+    }
+
+    /// Like [`Flotilla::get_collection`], but never answered from the cache without asking the server
+    pub async fn get_current_collection(&self, id: &String) -> Result<Collection>
+    {
+        self.fetch_current(&self.url(&format!("/shipyard/collection/{}", id))).await
+    }
+
+    /// Like [`Flotilla::get_ship`], but never answered from the cache without asking the server
+    pub async fn get_current_ship(&self, id: &String) -> Result<Ship>
+    {
+        self.fetch_current(&self.url(&format!("/shipyard/ship/{}", id))).await
+    }
+
+    pub async fn upload_ship(&self, file_name: &str, contents: String) -> Result<Ship>
+    {
+        let body = json!({
+            "fileName": file_name,
+            "shipFile": contents,
+        });
+        let ship: Ship = self.fetch(self.json_request(Method::POST, "/shipyard/ship", &body).await?).await?;
+        self.invalidate(None);
+        Ok(ship)
+    }
+
+    pub async fn update_ship_file(&self, id: &String, file_name: &str, contents: String) -> Result<Ship>
+    {
+        let body = json!({
+            "fileName": file_name,
+            "shipFile": contents,
+        });
+        let path = format!("/shipyard/ship/{}/file", id);
+        let ship: Ship = self.fetch(self.json_request(Method::PUT, &path, &body).await?).await?;
+        self.invalidate(Some(id));
+        Ok(ship)
+    }
+
+    pub async fn create_collection(&self, collection: &NewCollection) -> Result<Collection>
+    {
+        let created: Collection = self.fetch(self.json_request(Method::POST, "/shipyard/collection", &json!(collection)).await?).await?;
+        self.invalidate(None);
+        Ok(created)
+    }
+
+    pub async fn delete_by_id(&self, id: &String) -> Result<()>
+    {
+        match get_id_type(id)?
+        {
+            IdType::Collection => self.delete_collection(id).await,
+            IdType::Ship => self.delete_ship(id).await,
+        }
+    }
+
+    pub async fn delete_collection(&self, id: &String) -> Result<()>
+    {
+        let url = self.url(&format!("/shipyard/collection/{}", id));
+        self.send_retrying(self.request(Method::DELETE, &url).await?).await?;
+        self.invalidate(Some(id));
+        Ok(())
+    }
+
+    pub async fn delete_ship(&self, id: &String) -> Result<()>
+    {
+        let url = self.url(&format!("/shipyard/ship/{}", id));
+        self.send_retrying(self.request(Method::DELETE, &url).await?).await?;
+        self.invalidate(Some(id));
+        Ok(())
+    }
+
+    pub async fn get_public_collection(&self, id: &String) -> Result<Collection>
+    {
+        self.fetch_cached(&self.url(&format!("/shipyard/collection/public/{}", id)), true).await
+    }
+}
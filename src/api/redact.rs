@@ -0,0 +1,121 @@
+// Purpose: Hides credentials from HTTP traffic before it is logged
+
+use reqwest::header::HeaderMap;
+
+const SECRET_HEADERS: [&str; 3] = ["authorization", "cookie", "set-cookie"];
+const REDACTED: &str = "<redacted>";
+/// Longest body we log, so a large listing does not drown the rest of the output
+const MAX_BODY: usize = 4096;
+
+fn is_secret_key(key: &str) -> bool {
+    let key = key.to_ascii_lowercase();
+    key.contains("token") || key.contains("password")
+}
+
+/// A url without its query string, which for presigned downloads carries the signature
+pub fn url(url: &str) -> String {
+    match url.split_once('?') {
+        Some((base, _)) => format!("{}?{}", base, REDACTED),
+        None => url.to_string(),
+    }
+}
+
+/// An error message which may quote `url`, with the url as [`url`] logs it
+pub fn error(error: &dyn std::fmt::Display, url: &str) -> String {
+    error.to_string().replace(url, &self::url(url))
+}
+
+/// Header names and values, with credentials replaced
+pub fn headers(headers: &HeaderMap) -> String {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let value = match SECRET_HEADERS.contains(&name.as_str()) {
+                true => REDACTED,
+                false => value.to_str().unwrap_or("<binary>"),
+            };
+            format!("{}: {}", name, value)
+        })
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+fn redact_json(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if is_secret_key(key) {
+                    *value = serde_json::Value::String(REDACTED.to_string());
+                } else {
+                    redact_json(value);
+                }
+            }
+        },
+        serde_json::Value::Array(values) => values.iter_mut().for_each(redact_json),
+        _ => {},
+    }
+}
+
+/// A response body with any token or password fields replaced, cut to a readable length
+pub fn body(text: &str) -> String {
+    let text = match serde_json::from_str::<serde_json::Value>(text) {
+        Ok(mut json) => {
+            redact_json(&mut json);
+            json.to_string()
+        },
+        Err(_) => text.to_string(),
+    };
+    match text.char_indices().nth(MAX_BODY) {
+        Some((end, _)) => format!("{}... ({} bytes in total)", &text[..end], text.len()),
+        None => text,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn credential_headers_are_hidden() {
+        let mut map = HeaderMap::new();
+        map.insert("Authorization", HeaderValue::from_static("Bearer secret"));
+        map.insert("Set-Cookie", HeaderValue::from_static("session=secret"));
+        map.insert("Content-Type", HeaderValue::from_static("application/json"));
+        let logged = headers(&map);
+        assert!(!logged.contains("secret"), "{}", logged);
+        assert!(logged.contains("authorization: <redacted>"));
+        assert!(logged.contains("set-cookie: <redacted>"));
+        assert!(logged.contains("content-type: application/json"));
+    }
+
+    #[test]
+    fn token_and_password_fields_are_hidden() {
+        let logged = body(r#"{"AuthenticationResult":{"IdToken":"a","RefreshToken":"b","ExpiresIn":3600},"users":[{"password":"c","name":"bob"}]}"#);
+        let json: serde_json::Value = serde_json::from_str(&logged).unwrap();
+        assert_eq!(json["AuthenticationResult"]["IdToken"], REDACTED);
+        assert_eq!(json["AuthenticationResult"]["RefreshToken"], REDACTED);
+        assert_eq!(json["AuthenticationResult"]["ExpiresIn"], 3600);
+        assert_eq!(json["users"][0]["password"], REDACTED);
+        assert_eq!(json["users"][0]["name"], "bob");
+        assert_eq!(body("not json, token=x"), "not json, token=x");
+    }
+
+    #[test]
+    fn long_bodies_are_cut() {
+        let text = "é".repeat(MAX_BODY + 10);
+        let logged = body(&text);
+        assert!(logged.starts_with(&"é".repeat(MAX_BODY)));
+        assert!(logged.ends_with(&format!("... ({} bytes in total)", text.len())));
+        assert_eq!(body(&"x".repeat(MAX_BODY)), "x".repeat(MAX_BODY));
+    }
+
+    #[test]
+    fn query_strings_are_hidden() {
+        let signed = "https://bucket.example/ship.seria?X-Amz-Signature=abc&X-Amz-Expires=60";
+        assert_eq!(url(signed), "https://bucket.example/ship.seria?<redacted>");
+        assert_eq!(url("https://api.example/user"), "https://api.example/user");
+        let message = format!("error sending request for url ({})", signed);
+        assert_eq!(error(&message, signed), "error sending request for url (https://bucket.example/ship.seria?<redacted>)");
+    }
+}
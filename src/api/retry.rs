@@ -0,0 +1,156 @@
+// Purpose: Decides when and how long to wait before repeating a failed request
+
+use crate::config::Config;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use chacha20poly1305::aead::OsRng;
+use chacha20poly1305::aead::rand_core::RngCore;
+use reqwest::{Method, StatusCode};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Delay before the first retry; each further retry doubles it
+const BASE_DELAY: Duration = Duration::from_millis(500);
+const MAX_DELAY: Duration = Duration::from_secs(30);
+/// Longest `Retry-After` we are willing to wait for. Beyond it the request fails as it is
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(120);
+
+#[derive(Debug, Clone, Copy)]
+pub struct Policy {
+    /// Tries in total, including the first
+    pub attempts: u32,
+}
+
+impl Policy {
+    pub fn from_config(config: &Config) -> Policy {
+        Policy { attempts: config.retries.saturating_add(1) }
+    }
+
+    /// Only requests which can safely be repeated are retried. A DELETE is not: if the first one
+    /// went through, the retry would report the object as missing
+    pub fn allows(&self, method: &Method) -> bool {
+        matches!(*method, Method::GET | Method::HEAD | Method::PUT | Method::OPTIONS)
+    }
+
+    /// How long to wait after a failed `attempt` (counting from 1), or None to give up.
+    ///
+    /// Waits for `retry_after` when the server asked for it, else backs off exponentially with
+    /// jitter, so that concurrent downloads do not all come back at once.
+    pub fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Option<Duration> {
+        if attempt >= self.attempts
+        {
+            return None;
+        }
+        if let Some(wait) = retry_after
+        {
+            return (wait <= MAX_RETRY_AFTER).then_some(wait);
+        }
+        let backoff = BASE_DELAY.saturating_mul(1 << attempt.saturating_sub(1).min(16)).min(MAX_DELAY);
+        Some(backoff / 2 + backoff.mul_f64(jitter() / 2.0))
+    }
+}
+
+/// Whether the server is telling us to come back later, as opposed to refusing the request
+pub fn is_transient(status: StatusCode) -> bool {
+    matches!(status.as_u16(), 408 | 429 | 502 | 503 | 504)
+}
+
+/// The wait a 429 or 503 asks for, given in seconds or as an HTTP date
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>()
+    {
+        return Some(Duration::from_secs(seconds));
+    }
+    let at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    (at.with_timezone(&chrono::Utc) - chrono::Utc::now()).to_std().ok().or(Some(Duration::ZERO))
+}
+
+/// A number in [0, 1), random enough to spread out retries
+fn jitter() -> f64 {
+    let bits = OsRng.next_u64();
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Called before each retry with the attempt about to be made and the total allowed
+#[derive(Clone)]
+pub struct Hook(pub Arc<dyn Fn(u32, u32) + Send + Sync>);
+
+impl std::fmt::Debug for Hook {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("Hook")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn headers(retry_after: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_str(retry_after).unwrap());
+        headers
+    }
+
+    #[test]
+    fn delay_gives_up_after_the_last_attempt() {
+        let policy = Policy { attempts: 3 };
+        assert!(policy.delay(1, None).is_some());
+        assert!(policy.delay(2, None).is_some());
+        assert_eq!(policy.delay(3, None), None);
+        assert_eq!(Policy { attempts: 1 }.delay(1, None), None);
+    }
+
+    #[test]
+    fn delay_backs_off_with_jitter() {
+        let policy = Policy { attempts: u32::MAX };
+        for _ in 0..100
+        {
+            let first = policy.delay(1, None).unwrap();
+            assert!(first >= BASE_DELAY / 2 && first < BASE_DELAY, "{:?}", first);
+            let third = policy.delay(3, None).unwrap();
+            assert!(third >= BASE_DELAY * 2 && third < BASE_DELAY * 4, "{:?}", third);
+        }
+    }
+
+    #[test]
+    fn delay_is_capped_for_late_attempts() {
+        let policy = Policy { attempts: u32::MAX };
+        for attempt in [0, 7, 17, 64, u32::MAX - 1]
+        {
+            let wait = policy.delay(attempt, None).unwrap();
+            assert!(wait < MAX_DELAY, "{}: {:?}", attempt, wait);
+        }
+        assert!(policy.delay(64, None).unwrap() >= MAX_DELAY / 2);
+    }
+
+    #[test]
+    fn delay_honors_retry_after_up_to_the_cap() {
+        let policy = Policy { attempts: 5 };
+        assert_eq!(policy.delay(1, Some(Duration::from_secs(3))), Some(Duration::from_secs(3)));
+        assert_eq!(policy.delay(1, Some(MAX_RETRY_AFTER)), Some(MAX_RETRY_AFTER));
+        assert_eq!(policy.delay(1, Some(MAX_RETRY_AFTER + Duration::from_secs(1))), None);
+        assert_eq!(policy.delay(5, Some(Duration::from_secs(3))), None);
+    }
+
+    #[test]
+    fn retry_after_reads_seconds_and_dates() {
+        assert_eq!(retry_after(&headers("7")), Some(Duration::from_secs(7)));
+        assert_eq!(retry_after(&headers(" 120 ")), Some(Duration::from_secs(120)));
+        assert_eq!(retry_after(&headers("Wed, 21 Oct 2015 07:28:00 GMT")), Some(Duration::ZERO));
+        let later = (chrono::Utc::now() + chrono::Duration::seconds(60)).to_rfc2822();
+        let wait = retry_after(&headers(&later)).unwrap();
+        assert!(wait > Duration::from_secs(55) && wait <= Duration::from_secs(60), "{:?}", wait);
+        assert_eq!(retry_after(&headers("soon")), None);
+        assert_eq!(retry_after(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn delete_is_not_retried() {
+        let policy = Policy { attempts: 3 };
+        assert!(policy.allows(&Method::GET));
+        assert!(policy.allows(&Method::PUT));
+        assert!(!policy.allows(&Method::DELETE));
+        assert!(!policy.allows(&Method::POST));
+    }
+}
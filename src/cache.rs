@@ -0,0 +1,144 @@
+// Purpose: On-disk cache of ship, collection and user metadata, so repeated lookups skip the network
+
+use crate::error::FlotillaError;
+use chrono::Utc;
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// One cached response body, for an API path as seen by one user (or anonymously)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Entry {
+    pub endpoint: String,
+    /// Empty for anonymous requests, whose answers are the same for everyone
+    pub user: String,
+    pub path: String,
+    pub etag: Option<String>,
+    /// How long the server said the body stays fresh, in seconds
+    pub max_age: Option<i64>,
+    pub fetched_unix: i64,
+    pub body: String,
+}
+
+impl Entry {
+    pub fn new(endpoint: &str, user: &str, path: &str, body: String) -> Entry {
+        Entry {
+            endpoint: endpoint.to_string(),
+            user: user.to_string(),
+            path: path.to_string(),
+            etag: None,
+            max_age: None,
+            fetched_unix: Utc::now().timestamp(),
+            body,
+        }
+    }
+
+    /// Whether the entry can be used without asking the server, going by its max-age or else `ttl`
+    pub fn is_fresh(&self, ttl: u64) -> bool {
+        let age = Utc::now().timestamp() - self.fetched_unix;
+        age < self.max_age.unwrap_or(ttl as i64)
+    }
+
+    fn is_for(&self, endpoint: &str, user: &str, path: &str) -> bool {
+        self.endpoint == endpoint && self.user == user && self.path == path
+    }
+}
+
+/// What `flotilla cache stats` reports
+#[derive(Serialize, Debug)]
+pub struct Stats {
+    pub location: String,
+    pub entries: usize,
+    pub bytes: u64,
+    pub fresh: usize,
+    pub stale: usize,
+}
+
+pub fn location() -> PathBuf {
+    let dirs = ProjectDirs::from("io", "Jodavaho", "Flotilla").expect("Application Error: Could not load cache directory. Please file a bug!");
+    dirs.cache_dir().join("metadata")
+}
+
+/// A stable file name for a key (FNV-1a), so entries survive upgrades of the standard library
+fn file_for(endpoint: &str, user: &str, path: &str) -> PathBuf {
+    let key = format!("{}\n{}\n{}", endpoint, user, path);
+    let hash = key.bytes().fold(0xcbf29ce484222325u64, |h, b| (h ^ b as u64).wrapping_mul(0x100000001b3));
+    location().join(format!("{:016x}.json", hash))
+}
+
+/// Every entry on disk, with the size of its file. Unreadable files are skipped
+fn entries() -> Vec<(PathBuf, u64, Entry)> {
+    let dir = match std::fs::read_dir(location())
+    {
+        Ok(dir) => dir,
+        Err(_) => return Vec::new(),
+    };
+    dir.filter_map(|f| f.ok())
+        .map(|f| f.path())
+        .filter(|p| p.extension().is_some_and(|x| x == "json"))
+        .filter_map(|p| {
+            let contents = std::fs::read_to_string(&p).ok()?;
+            let entry = serde_json::from_str(&contents).ok()?;
+            Some((p, contents.len() as u64, entry))
+        })
+        .collect()
+}
+
+/// The cached entry for a path, fresh or not
+pub fn load(endpoint: &str, user: &str, path: &str) -> Option<Entry> {
+    let contents = std::fs::read_to_string(file_for(endpoint, user, path)).ok()?;
+    serde_json::from_str::<Entry>(&contents)
+        .ok()
+        .filter(|e| e.is_for(endpoint, user, path))
+}
+
+/// Writes an entry, replacing any earlier one for the same path
+pub fn store(entry: &Entry) -> Result<(), FlotillaError> {
+    let dir = location();
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| FlotillaError::Io(format!("Could not create {}: {}", dir.display(), e)))?;
+    let file = file_for(&entry.endpoint, &entry.user, &entry.path);
+    // Written aside and renamed, so concurrent readers never see half an entry
+    let partial = file.with_extension(format!("{}.tmp", std::process::id()));
+    let contents = serde_json::to_string(entry).expect("Application Error: Could not serialize cache entry. Please file a bug!");
+    std::fs::write(&partial, contents)
+        .and_then(|_| std::fs::rename(&partial, &file))
+        .map_err(|e| FlotillaError::Io(format!("Could not write {}: {}", file.display(), e)))
+}
+
+/// Drops whatever may be stale after a change on `endpoint`: the listings of our own objects and,
+/// if given, every entry for the object `id` and every collection which lists it
+pub fn invalidate(endpoint: &str, id: Option<&str>) {
+    let is_stale = |e: &Entry| e.path == "/user" || id.is_some_and(|id| {
+        e.path.ends_with(&format!("/{}", id)) || (e.path.contains("/collection/") && e.body.contains(id))
+    });
+    entries()
+        .into_iter()
+        .filter(|(_, _, e)| e.endpoint == endpoint && is_stale(e))
+        .for_each(|(file, _, _)| { let _ = std::fs::remove_file(file); });
+}
+
+/// Removes every entry, returning how many there were
+pub fn clear() -> Result<usize, FlotillaError> {
+    let count = entries().len();
+    match std::fs::remove_dir_all(location())
+    {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            Err(FlotillaError::Io(format!("Could not remove {}: {}", location().display(), e)))
+        },
+        _ => Ok(count),
+    }
+}
+
+/// Counts the entries, and how many are fresh going by `ttl`
+pub fn stats(ttl: u64) -> Stats {
+    let entries = entries();
+    let fresh = entries.iter().filter(|(_, _, e)| e.is_fresh(ttl)).count();
+    Stats {
+        location: location().display().to_string(),
+        entries: entries.len(),
+        bytes: entries.iter().map(|(_, size, _)| size).sum(),
+        fresh,
+        stale: entries.len() - fresh,
+    }
+}
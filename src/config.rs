@@ -1,34 +1,109 @@
 
+use crate::credentials;
+use crate::error::FlotillaError;
 use directories::ProjectDirs;
 use directories::UserDirs;
+use std::path::PathBuf;
 
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Config {
     pub username: String,
+    /// Only set when given on the command line or in the environment. See [`Config::resolve_password`]
     pub password: String,
     pub endpoint: String,
     pub download_path: String,
+    /// The game's ships folder, which `get --extract` installs into
+    pub ships_dir: Option<String>,
+    /// How downloads are named, see `get --name-template`
+    pub name_template: Option<String>,
+    /// A config file to use instead of the default one
+    pub file: Option<PathBuf>,
+    /// The `[profile.NAME]` section which overrides the defaults, if any
+    pub profile: Option<String>,
+    /// How long cached metadata is used without asking the server, in seconds, unless the server says otherwise
+    pub cache_ttl: u64,
+    /// Answer metadata requests from the cache only, never from the network
+    pub offline: bool,
+    /// How often a failed idempotent request is repeated before giving up
+    pub retries: u32,
 }
 
+/// The user's Downloads folder, or their home directory on systems without one
+fn default_download_path() -> String {
+    let dirs = UserDirs::new().expect("Application Error: Could not find your home directory. Please file a bug!");
+    dirs.download_dir()
+        .unwrap_or(dirs.home_dir())
+        .to_str()
+        .unwrap()
+        .to_string()
+}
 
+fn parse_number<T: std::str::FromStr>(key: &str, value: &str, config_file: &str) -> Result<T, FlotillaError> {
+    value
+        .parse()
+        .map_err(|_| FlotillaError::ConfigIo(format!("Invalid {} '{}' in {}: expected a whole number", key, value, config_file)))
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl Config {
-    #[allow(dead_code)]
     pub fn new() -> Config {
         Config {
             username: String::from(""),
             password: String::from(""),
             endpoint: String::from("https://api.jodavaho.io/hfopt/v2"),
-            download_path: UserDirs::new().unwrap().download_dir().expect("Cannot set download_dir! Please file a bug.").to_str().unwrap().to_string(),
+            download_path: default_download_path(),
+            ships_dir: None,
+            name_template: None,
+            file: None,
+            profile: None,
+            cache_ttl: 300,
+            offline: false,
+            retries: 3,
         }
     }
 
     pub fn location(&self) -> String {
+        if let Some(file) = &self.file
+        {
+            return file.display().to_string();
+        }
         let config_dir = ProjectDirs::from("io", "Jodavaho", "Flotilla").expect("Application Error: Could not load configuration directory. Please file a bug!");
         format!("{}/config.ini", config_dir.config_dir().to_str().unwrap())
     }
 
+    /// Reads and writes `file` instead of the default config file, if given
+    pub fn with_file(mut self, file: Option<PathBuf>) -> Self {
+        if file.is_some()
+        {
+            self.file = file;
+        }
+        self
+    }
+
+    /// Selects a profile, falling back to `FLOTILLA_PROFILE` if none is given
+    pub fn with_profile(mut self, profile: Option<String>) -> Self {
+        self.profile = profile
+            .or_else(|| std::env::var("FLOTILLA_PROFILE").ok())
+            .filter(|p| !p.is_empty());
+        self
+    }
+
+    /// Serves metadata from the cache only
+    pub fn with_offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    fn profile_section(&self) -> Option<String> {
+        self.profile.as_ref().map(|p| format!("profile.{}", p))
+    }
+
     pub fn load_env(mut self) -> Self {
         if std::env::var("FLOTILLA_ENDPOINT").is_ok()
         {
@@ -42,33 +117,61 @@ impl Config {
         {
             self.password = std::env::var("FLOTILLA_PASSWORD").unwrap();
         }
+        if let Some(ttl) = std::env::var("FLOTILLA_CACHE_TTL").ok().and_then(|t| t.parse().ok())
+        {
+            self.cache_ttl = ttl;
+        }
+        if let Ok(download_path) = std::env::var("FLOTILLA_DOWNLOAD_PATH")
+        {
+            self.download_path = download_path;
+        }
+        if let Ok(ships_dir) = std::env::var("FLOTILLA_SHIPS_DIR")
+        {
+            self.ships_dir = Some(ships_dir);
+        }
+        if let Some(retries) = std::env::var("FLOTILLA_RETRIES").ok().and_then(|r| r.parse().ok())
+        {
+            self.retries = retries;
+        }
         self
     }
 
-    pub fn load_file(mut self) -> Result<Self, ini::Error> {
+    pub fn load_file(mut self) -> Result<Self, FlotillaError> {
 
         let config_file = self.location();
         let contents = match ini::Ini::load_from_file(&config_file)
         {
             Ok(contents) => contents,
             Err(x) => {
-                eprintln!("Error loading config file: {:?}", x);
-                return Err(x);
+                return Err(FlotillaError::ConfigIo(format!("Could not load {}: {}", config_file, x)));
             }
         };
 
-        //does the config have a username and password?
+        //does the config have a username?
         if let Some(user) = contents.section(Some("user".to_owned()))
         {
-            if user.contains_key("username") && user.contains_key("password")
+            if user.contains_key("username")
             {
                 self.username = user.get("username").unwrap().to_owned();
+            }
+            // Older versions kept the password here in clear text
+            if user.contains_key("password")
+            {
+                tracing::warn!("{} contains a plaintext password. Run setup again to move it to the credential store.", config_file);
                 self.password = user.get("password").unwrap().to_owned();
             }
             if user.contains_key("download_path")
             {
                 self.download_path = user.get("download_path").unwrap().to_owned();
             }
+            if let Some(ships_dir) = user.get("ships_dir")
+            {
+                self.ships_dir = Some(ships_dir.to_owned());
+            }
+            if let Some(template) = user.get("name_template")
+            {
+                self.name_template = Some(template.to_owned());
+            }
         }
 
         if let Some(api) = contents.section(Some("api".to_owned()))
@@ -77,6 +180,14 @@ impl Config {
             {
                 self.endpoint = api.get("endpoint").unwrap().to_owned();
             }
+            if let Some(ttl) = api.get("cache_ttl")
+            {
+                self.cache_ttl = parse_number("cache_ttl", ttl, &config_file)?;
+            }
+            if let Some(retries) = api.get("retries")
+            {
+                self.retries = parse_number("retries", retries, &config_file)?;
+            }
         }
 
         if let Some(endpoint) = contents.section(Some("api".to_owned()))
@@ -89,9 +200,33 @@ impl Config {
             self.endpoint = String::from("https://api.jodavaho.io/hfoptpreview/v2");
         }
 
+        if let Some(section) = self.profile_section()
+        {
+            let profile = match contents.section(Some(section.clone()))
+            {
+                Some(profile) => profile,
+                None => return Err(FlotillaError::ConfigIo(format!("No [{}] section in {}", section, config_file))),
+            };
+            for (key, value) in profile.iter()
+            {
+                match key
+                {
+                    "username" => self.username = value.to_owned(),
+                    "endpoint" => self.endpoint = value.to_owned(),
+                    "download_path" => self.download_path = value.to_owned(),
+                    "ships_dir" => self.ships_dir = Some(value.to_owned()),
+                    "name_template" => self.name_template = Some(value.to_owned()),
+                    "cache_ttl" => self.cache_ttl = parse_number(key, value, &config_file)?,
+                    "retries" => self.retries = parse_number(key, value, &config_file)?,
+                    _ => {},
+                }
+            }
+        }
+
         Ok(self)
     }
 
+    #[allow(clippy::wrong_self_convention)]
     pub fn from_options(mut self, username:Option<String>, password:Option<String>, endpoint:Option<String>) -> Self {
         if let Some(username) = username
         {
@@ -108,21 +243,34 @@ impl Config {
         self
     }
 
-    pub fn remove(self) -> Result<Self, String> {
+    pub fn remove(self) -> Result<Self, FlotillaError> {
         match std::fs::remove_file(self.location())
         {
             Ok(_) => Ok(self),
-            Err(x) => Err(x.to_string())
+            Err(x) => Err(FlotillaError::ConfigIo(format!("Could not remove {}: {}", self.location(), x)))
         }
     }
 
-    pub fn load_all(self, username_override:Option<String>, password_override:Option<String>, endpoint_override:Option<String>) -> Self {
-        self.load_file().unwrap().load_env().from_options(username_override, password_override, endpoint_override)
+    pub fn load_all(self, username_override:Option<String>, password_override:Option<String>, endpoint_override:Option<String>) -> Result<Self, FlotillaError> {
+        Ok(self.load_file()?.load_env().from_options(username_override, password_override, endpoint_override))
     }
 
-    pub fn save_to_default(&self, ) -> Result<&Config, std::io::Error>{
-        let config_dir = ProjectDirs::from("io", "Jodavaho", "Flotilla").expect("Application Error: Could not load configuration directory. Please file a bug!");
-        let config_file = config_dir.config_dir().join("config.ini");
+    /// The account password: as given on the command line or in the environment, or else
+    /// decrypted from the credential store. `None` if there is no password anywhere
+    pub fn resolve_password(&self) -> Result<Option<String>, FlotillaError> {
+        if !self.password.is_empty()
+        {
+            return Ok(Some(self.password.clone()));
+        }
+        if self.username.is_empty() || !credentials::contains(&self.endpoint, &self.username)
+        {
+            return Ok(None);
+        }
+        credentials::retrieve(&self.endpoint, &self.username, &credentials::passphrase(false)?)
+    }
+
+    pub fn save_to_default(&self, ) -> Result<&Config, FlotillaError>{
+        let config_file = PathBuf::from(self.location());
         let mut contents = match ini::Ini::load_from_file(&config_file)
         {
             Ok(contents) => contents,
@@ -131,23 +279,36 @@ impl Config {
             }
         };
 
-        contents.with_section(Some("user".to_owned()))
-            .set("username", self.username.clone())
-            .set("password", self.password.clone())
-            .set("download_path", self.download_path.clone());
-            
+        match self.profile_section()
+        {
+            Some(section) => {
+                contents.with_section(Some(section))
+                    .set("username", self.username.clone())
+                    .set("download_path", self.download_path.clone())
+                    .set("endpoint", self.endpoint.clone());
+            },
+            None => {
+                contents.with_section(Some("user".to_owned()))
+                    .set("username", self.username.clone())
+                    .set("download_path", self.download_path.clone());
+                contents.delete_from(Some("user"), "password");
 
-        contents.with_section(Some("api".to_owned()))
-            .set("endpoint", self.endpoint.clone());
+                contents.with_section(Some("api".to_owned()))
+                    .set("endpoint", self.endpoint.clone());
+            },
+        }
 
         //make sure the config directory exists
-        std::fs::create_dir_all(config_dir.config_dir()).expect("Application Error: Could not create configuration directory. Please file a bug!");
-        println!("Writing to {:?}", config_file);
+        if let Some(config_dir) = config_file.parent().filter(|d| !d.as_os_str().is_empty())
+        {
+            std::fs::create_dir_all(config_dir)
+                .map_err(|e| FlotillaError::ConfigIo(format!("Could not create {}: {}", config_dir.display(), e)))?;
+        }
 
         match contents.write_to_file(&config_file)
         {
             Ok(_) => Ok(self),
-            Err(e) => Err(e)
+            Err(e) => Err(FlotillaError::ConfigIo(format!("Could not write {}: {}", config_file.display(), e)))
         }
     }
 }
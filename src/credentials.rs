@@ -0,0 +1,161 @@
+// Purpose: Encrypted local storage for account passwords, so config.ini never holds them in clear text
+
+use crate::error::FlotillaError;
+use argon2::Argon2;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Every stored password, by endpoint and username (see [`key`]). Each is encrypted with a key
+/// derived from the passphrase
+#[derive(Serialize, Deserialize, Default)]
+struct Store {
+    entries: HashMap<String, Entry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+pub fn location() -> PathBuf {
+    let config_dir = ProjectDirs::from("io", "Jodavaho", "Flotilla").expect("Application Error: Could not load configuration directory. Please file a bug!");
+    config_dir.config_dir().join("credentials.json")
+}
+
+fn load() -> Result<Store, FlotillaError> {
+    let path = location();
+    match std::fs::read_to_string(&path)
+    {
+        Ok(contents) => serde_json::from_str(&contents)
+            .map_err(|e| FlotillaError::ConfigIo(format!("Could not read {}: {}", path.display(), e))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Store::default()),
+        Err(e) => Err(FlotillaError::ConfigIo(format!("Could not read {}: {}", path.display(), e))),
+    }
+}
+
+fn save(store: &Store) -> Result<(), FlotillaError> {
+    let path = location();
+    if let Some(dir) = path.parent()
+    {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| FlotillaError::ConfigIo(format!("Could not create {}: {}", dir.display(), e)))?;
+    }
+    let contents = serde_json::to_string_pretty(store).expect("Application Error: Could not serialize credentials. Please file a bug!");
+    std::fs::write(&path, contents)
+        .map_err(|e| FlotillaError::ConfigIo(format!("Could not write {}: {}", path.display(), e)))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))
+            .map_err(|e| FlotillaError::ConfigIo(format!("Could not restrict permissions of {}: {}", path.display(), e)))?;
+    }
+    Ok(())
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<Key, FlotillaError> {
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| FlotillaError::Other(format!("Could not derive a key from the passphrase: {}", e)))?;
+    Ok(key)
+}
+
+/// Where a password is filed. The same account name can exist on several endpoints
+fn key(endpoint: &str, username: &str) -> String {
+    format!("{} {}", endpoint, username)
+}
+
+impl Store {
+    /// The entry for an account, or else one stored by an older version under the username alone
+    fn find(&self, endpoint: &str, username: &str) -> Option<&Entry> {
+        self.entries.get(&key(endpoint, username)).or_else(|| self.entries.get(username))
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Whether a password is stored for this account
+pub fn contains(endpoint: &str, username: &str) -> bool {
+    load().map(|store| store.find(endpoint, username).is_some()).unwrap_or(false)
+}
+
+/// Encrypts and stores the password for an account, replacing any stored before
+pub fn store(endpoint: &str, username: &str, password: &str, passphrase: &str) -> Result<(), FlotillaError> {
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = ChaCha20Poly1305::new(&derive_key(passphrase, &salt)?)
+        .encrypt(&nonce, password.as_bytes())
+        .map_err(|_| FlotillaError::Other("Could not encrypt the password".to_string()))?;
+
+    let mut store = load()?;
+    store.entries.insert(key(endpoint, username), Entry {
+        salt: to_hex(&salt),
+        nonce: to_hex(&nonce),
+        ciphertext: to_hex(&ciphertext),
+    });
+    save(&store)
+}
+
+/// Decrypts the stored password for an account, if there is one
+pub fn retrieve(endpoint: &str, username: &str, passphrase: &str) -> Result<Option<String>, FlotillaError> {
+    let store = load()?;
+    let entry = match store.find(endpoint, username)
+    {
+        Some(entry) => entry,
+        None => return Ok(None),
+    };
+    let corrupt = || FlotillaError::ConfigIo(format!("The stored password for {} in {} is corrupt. Please run setup again.", username, location().display()));
+    let salt = from_hex(&entry.salt).ok_or_else(corrupt)?;
+    let nonce = from_hex(&entry.nonce).filter(|n| n.len() == 12).ok_or_else(corrupt)?;
+    let ciphertext = from_hex(&entry.ciphertext).ok_or_else(corrupt)?;
+    let plaintext = ChaCha20Poly1305::new(&derive_key(passphrase, &salt)?)
+        .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+        .map_err(|_| FlotillaError::ConfigIo("Could not decrypt the stored password. Is the passphrase correct?".to_string()))?;
+    String::from_utf8(plaintext).map(Some).map_err(|_| corrupt())
+}
+
+/// Removes the stored password for an account. Not having one is not an error
+pub fn forget(endpoint: &str, username: &str) -> Result<(), FlotillaError> {
+    let mut store = load()?;
+    let removed = store.entries.remove(&key(endpoint, username)).is_some();
+    if store.entries.remove(username).is_some() || removed
+    {
+        save(&store)?;
+    }
+    Ok(())
+}
+
+/// The passphrase protecting stored passwords, from `FLOTILLA_PASSPHRASE` or asked for on the terminal.
+///
+/// With `confirm`, a typed passphrase must be entered twice, as when storing a new password.
+pub fn passphrase(confirm: bool) -> Result<String, FlotillaError> {
+    if let Ok(passphrase) = std::env::var("FLOTILLA_PASSPHRASE")
+    {
+        return Ok(passphrase);
+    }
+    let read = |prompt: &str| rpassword::prompt_password(prompt)
+        .map_err(|e| FlotillaError::Io(format!("Could not read the passphrase: {}", e)));
+    let passphrase = read("Passphrase for stored credentials: ")?;
+    if confirm && passphrase != read("Repeat passphrase: ")?
+    {
+        return Err(FlotillaError::Usage("Passphrases do not match".to_string()));
+    }
+    Ok(passphrase)
+}
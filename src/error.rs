@@ -0,0 +1,189 @@
+// Purpose: The error type shared by the library and the CLI
+
+use std::fmt;
+
+/// Everything that can go wrong talking to HFOPT or handling local files.
+///
+/// Each variant maps to a distinct process exit code (see [`FlotillaError::exit_code`]) so scripts
+/// can tell, for example, "not logged in" from "server down".
+#[derive(Debug)]
+pub enum FlotillaError {
+    /// Anything without a more specific kind
+    Other(String),
+    /// The command line was used incorrectly
+    Usage(String),
+    /// The server could not be reached, or the connection failed
+    Network(String),
+    /// The server answered with an unexpected status
+    Http { status: u16, body: String },
+    /// The session is missing, expired or was rejected by the server
+    AuthExpired,
+    /// The requested object does not exist (or is not visible to us)
+    NotFound(String),
+    /// A response or file could not be understood
+    Parse { message: String, snippet: String },
+    /// The configuration or session file could not be read or written
+    ConfigIo(String),
+    /// An id which is not a valid ship or collection id
+    InvalidId(String),
+    /// A short id, prefix or name which matches more than one object
+    Ambiguous { query: String, candidates: Vec<String> },
+    /// A local file could not be read or written
+    Io(String),
+    /// A file failed validation
+    Validation(String),
+    /// Wraps another error with a description of what we were doing
+    Context { message: String, source: Box<FlotillaError> },
+    /// Several independent operations failed
+    Multiple(Vec<FlotillaError>),
+}
+
+pub type Result<T> = std::result::Result<T, FlotillaError>;
+
+impl FlotillaError {
+    /// The process exit code for this error
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            FlotillaError::Other(_) => 1,
+            FlotillaError::Usage(_) => 2,
+            FlotillaError::Network(_) => 3,
+            FlotillaError::Http { .. } => 4,
+            FlotillaError::AuthExpired => 5,
+            FlotillaError::NotFound(_) => 6,
+            FlotillaError::Parse { .. } => 7,
+            FlotillaError::ConfigIo(_) => 8,
+            FlotillaError::InvalidId(_) => 9,
+            FlotillaError::Io(_) => 10,
+            FlotillaError::Validation(_) => 11,
+            FlotillaError::Ambiguous { .. } => 12,
+            FlotillaError::Context { source, .. } => source.exit_code(),
+            FlotillaError::Multiple(errors) => {
+                let mut codes = errors.iter().map(|e| e.exit_code());
+                match codes.next() {
+                    Some(first) if codes.all(|c| c == first) => first,
+                    _ => 1,
+                }
+            },
+        }
+    }
+
+    /// A stable, machine-readable name for the kind of error
+    pub fn kind(&self) -> &'static str {
+        match self {
+            FlotillaError::Other(_) => "other",
+            FlotillaError::Usage(_) => "usage",
+            FlotillaError::Network(_) => "network",
+            FlotillaError::Http { .. } => "http",
+            FlotillaError::AuthExpired => "auth_expired",
+            FlotillaError::NotFound(_) => "not_found",
+            FlotillaError::Parse { .. } => "parse",
+            FlotillaError::ConfigIo(_) => "config_io",
+            FlotillaError::InvalidId(_) => "invalid_id",
+            FlotillaError::Io(_) => "io",
+            FlotillaError::Validation(_) => "validation",
+            FlotillaError::Ambiguous { .. } => "ambiguous_id",
+            FlotillaError::Context { source, .. } => source.kind(),
+            FlotillaError::Multiple(_) => "multiple",
+        }
+    }
+
+    /// This error as a JSON object with `kind`, `code` and `message`, plus `status`, `body`,
+    /// `snippet`, `candidates` or `errors` where they apply
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut json = serde_json::json!({
+            "kind": self.kind(),
+            "code": self.exit_code(),
+            "message": self.to_string(),
+        });
+        let mut inner = self;
+        while let FlotillaError::Context { source, .. } = inner {
+            inner = source;
+        }
+        match inner {
+            FlotillaError::Http { status, body } => {
+                json["status"] = serde_json::json!(status);
+                json["body"] = serde_json::json!(body);
+            },
+            FlotillaError::Parse { snippet, .. } => json["snippet"] = serde_json::json!(snippet),
+            FlotillaError::Ambiguous { candidates, .. } => json["candidates"] = serde_json::json!(candidates),
+            FlotillaError::Multiple(errors) => json["errors"] = errors.iter().map(|e| e.to_json()).collect(),
+            _ => {},
+        }
+        json
+    }
+
+    /// Describes what we were doing when this error happened
+    pub fn context<S: Into<String>>(self, message: S) -> FlotillaError {
+        FlotillaError::Context { message: message.into(), source: Box::new(self) }
+    }
+
+    /// A parse error which keeps the start of the offending payload for bug reports
+    pub fn parse<E: fmt::Display>(error: E, payload: &str) -> FlotillaError {
+        FlotillaError::Parse {
+            message: error.to_string(),
+            snippet: payload.chars().take(200).collect(),
+        }
+    }
+
+    /// Combines the errors of several operations, if there were any
+    pub fn from_many(mut errors: Vec<FlotillaError>) -> Result<()> {
+        match errors.len() {
+            0 => Ok(()),
+            1 => Err(errors.remove(0)),
+            _ => Err(FlotillaError::Multiple(errors)),
+        }
+    }
+}
+
+impl fmt::Display for FlotillaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FlotillaError::Other(msg) => write!(f, "{}", msg),
+            FlotillaError::Usage(msg) => write!(f, "{}", msg),
+            FlotillaError::Network(msg) => write!(f, "Network error: {}", msg),
+            FlotillaError::Http { status, body } => write!(f, "Server returned HTTP {}: {}", status, body),
+            FlotillaError::AuthExpired => write!(f, "Session expired. Please login."),
+            FlotillaError::NotFound(what) => write!(f, "Not found: {}", what),
+            FlotillaError::Parse { message, snippet } => write!(f, "Could not parse response: {} (payload starts with: {:?})", message, snippet),
+            FlotillaError::ConfigIo(msg) => write!(f, "Configuration error: {}", msg),
+            FlotillaError::InvalidId(id) => write!(f, "Invalid id: {} (expected a 32 character collection id or a 64 character ship id)", id),
+            FlotillaError::Io(msg) => write!(f, "{}", msg),
+            FlotillaError::Validation(msg) => write!(f, "{}", msg),
+            FlotillaError::Ambiguous { query, candidates } => {
+                write!(f, "'{}' matches more than one object. Please use one of:", query)?;
+                candidates.iter().try_for_each(|c| write!(f, "\n  {}", c))
+            },
+            FlotillaError::Context { message, source } => write!(f, "{}: {}", message, source),
+            FlotillaError::Multiple(errors) => {
+                let lines: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+                write!(f, "{}", lines.join("\n"))
+            },
+        }
+    }
+}
+
+impl std::error::Error for FlotillaError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FlotillaError::Context { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for FlotillaError {
+    fn from(e: reqwest::Error) -> Self {
+        match e.status() {
+            Some(status) if status.as_u16() == 401 => FlotillaError::AuthExpired,
+            Some(status) if status.as_u16() == 404 => FlotillaError::NotFound(e.url().map(|u| u.path().to_string()).unwrap_or_default()),
+            Some(status) => FlotillaError::Http { status: status.as_u16(), body: e.without_url().to_string() },
+            None => FlotillaError::Network(e.without_url().to_string()),
+        }
+    }
+}
+
+impl From<ini::Error> for FlotillaError {
+    fn from(e: ini::Error) -> Self {
+        FlotillaError::ConfigIo(e.to_string())
+    }
+}
@@ -0,0 +1,186 @@
+// Purpose: Unpacks downloaded collections and ships into the game's ships folder
+
+use crate::error::FlotillaError;
+use serde::Serialize;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// What to do when a ship of the same name, but different contents, is already installed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Collision {
+    /// Install under a new name such as `Ship (2).seria`
+    Rename,
+    Skip,
+    Overwrite,
+}
+
+impl FromStr for Collision {
+    type Err = FlotillaError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rename" => Ok(Collision::Rename),
+            "skip" => Ok(Collision::Skip),
+            "overwrite" => Ok(Collision::Overwrite),
+            _ => Err(FlotillaError::Usage(format!("Unknown collision policy '{}'. Please use rename, skip or overwrite", s))),
+        }
+    }
+}
+
+/// What happened to one ship
+#[derive(Serialize, Debug, Clone)]
+pub struct Installed {
+    /// The ship's file name in the download
+    pub ship: String,
+    /// Where it is installed now, if it is
+    pub path: Option<String>,
+    /// installed, unchanged, renamed, overwritten or skipped
+    pub action: &'static str,
+}
+
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL",
+    "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9",
+    "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// A file name which is safe on every platform: no path separators, no characters Windows
+/// rejects, no control characters, no leading or trailing dots and spaces, and no reserved
+/// device names. Never empty
+pub fn sanitize_file_name(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let cleaned = cleaned.trim_matches(|c: char| c == '.' || c.is_whitespace()).to_string();
+    let stem = cleaned.split('.').next().unwrap_or_default();
+    match cleaned.as_str()
+    {
+        "" => "_".to_string(),
+        _ if RESERVED_NAMES.iter().any(|r| r.eq_ignore_ascii_case(stem)) => format!("_{}", cleaned),
+        _ => cleaned,
+    }
+}
+
+fn is_seria(name: &str) -> bool {
+    Path::new(name).extension().is_some_and(|x| x.eq_ignore_ascii_case("seria"))
+}
+
+/// Installs ships into one folder
+#[derive(Debug, Clone)]
+pub struct Installer {
+    pub dir: PathBuf,
+    pub collision: Collision,
+}
+
+impl Installer {
+    /// Installs every ship in a downloaded collection zip, or a downloaded ship file
+    pub fn install(&self, download: &Path) -> Result<Vec<Installed>, FlotillaError> {
+        std::fs::create_dir_all(&self.dir)
+            .map_err(|e| FlotillaError::Io(format!("Could not create {}: {}", self.dir.display(), e)))?;
+        let read_error = |e: std::io::Error| FlotillaError::Io(format!("Could not read {}: {}", download.display(), e));
+        let name = download.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+        if is_seria(name)
+        {
+            let contents = std::fs::read(download).map_err(read_error)?;
+            return Ok(vec![self.place(name, &contents)?]);
+        }
+
+        let file = std::fs::File::open(download).map_err(read_error)?;
+        let mut archive = zip::ZipArchive::new(file)
+            .map_err(|e| FlotillaError::Validation(format!("{} is not a readable zip archive: {}", download.display(), e)))?;
+        let mut installed = Vec::new();
+        for index in 0..archive.len()
+        {
+            let mut entry = archive.by_index(index)
+                .map_err(|e| FlotillaError::Validation(format!("{}: entry {}: {}", download.display(), index, e)))?;
+            // Only the file name counts; the folders inside the archive are neither trusted nor kept
+            let name = match entry.enclosed_name().and_then(|p| p.file_name()).and_then(|n| n.to_str())
+            {
+                Some(name) if !entry.is_dir() && is_seria(name) => name.to_string(),
+                _ => continue,
+            };
+            let mut contents = Vec::new();
+            entry.read_to_end(&mut contents)
+                .map_err(|e| FlotillaError::Validation(format!("{}: {}: {}", download.display(), name, e)))?;
+            installed.push(self.place(&name, &contents)?);
+        }
+        Ok(installed)
+    }
+
+    /// Writes one ship into the folder, following the collision policy
+    fn place(&self, name: &str, contents: &[u8]) -> Result<Installed, FlotillaError> {
+        let file_name = sanitize_file_name(name);
+        let target = self.dir.join(&file_name);
+        let same = |path: &Path| std::fs::read(path).is_ok_and(|existing| existing == contents);
+        let (path, action) = match (target.exists(), self.collision)
+        {
+            (false, _) => (Some(target), "installed"),
+            (true, _) if same(&target) => return Ok(Installed { ship: name.to_string(), path: Some(target.display().to_string()), action: "unchanged" }),
+            (true, Collision::Skip) => (None, "skipped"),
+            (true, Collision::Overwrite) => (Some(target), "overwritten"),
+            (true, Collision::Rename) => {
+                let path = Path::new(&file_name);
+                let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+                let extension = path.extension().and_then(|x| x.to_str()).unwrap_or("seria");
+                let candidate = (2..)
+                    .map(|n| self.dir.join(format!("{} ({}).{}", stem, n, extension)))
+                    .find(|p| !p.exists() || same(p))
+                    .unwrap();
+                if candidate.exists()
+                {
+                    return Ok(Installed { ship: name.to_string(), path: Some(candidate.display().to_string()), action: "unchanged" });
+                }
+                (Some(candidate), "renamed")
+            },
+        };
+        if let Some(path) = &path
+        {
+            // Written aside and renamed, so the game never sees half a ship
+            let partial = path.with_extension("seria.part");
+            std::fs::write(&partial, contents)
+                .and_then(|_| std::fs::rename(&partial, path))
+                .map_err(|e| FlotillaError::Io(format!("Could not write {}: {}", path.display(), e)))?;
+        }
+        Ok(Installed {
+            ship: name.to_string(),
+            path: path.map(|p| p.display().to_string()),
+            action,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sanitize_keeps_ordinary_names() {
+        assert_eq!(sanitize_file_name("My Ship.seria"), "My Ship.seria");
+    }
+
+    #[test]
+    fn sanitize_replaces_separators_and_reserved_characters() {
+        assert_eq!(sanitize_file_name("../a/b\\c:d*e?.seria"), "_a_b_c_d_e_.seria");
+        assert_eq!(sanitize_file_name("tab\there"), "tab_here");
+    }
+
+    #[test]
+    fn sanitize_trims_dots_and_spaces() {
+        assert_eq!(sanitize_file_name("  .hidden. "), "hidden");
+        assert_eq!(sanitize_file_name(" . "), "_");
+        assert_eq!(sanitize_file_name(""), "_");
+    }
+
+    #[test]
+    fn sanitize_avoids_device_names() {
+        assert_eq!(sanitize_file_name("CON"), "_CON");
+        assert_eq!(sanitize_file_name("com1.seria"), "_com1.seria");
+        assert_eq!(sanitize_file_name("CONSOLE.seria"), "CONSOLE.seria");
+    }
+}
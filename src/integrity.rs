@@ -0,0 +1,151 @@
+// Purpose: Checks downloaded archives and ships, and remembers their checksums in a manifest
+
+use crate::error::FlotillaError;
+use crate::seria;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Name of the manifest kept in each download folder
+pub const MANIFEST: &str = ".flotilla-manifest.json";
+
+/// What checking one downloaded file found
+#[derive(Serialize, Debug, Clone)]
+pub struct Check {
+    pub sha256: String,
+    /// Files inside the archive, or 1 for a ship file
+    pub entries: usize,
+    /// .seria files among them
+    pub ships: usize,
+    pub problems: Vec<String>,
+}
+
+impl Check {
+    pub fn is_valid(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Whether a file is something `get` downloads, and so something we can check
+pub fn is_checkable(path: &Path) -> bool {
+    matches!(extension(path).as_deref(), Some("zip") | Some("seria"))
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension().and_then(|x| x.to_str()).map(|x| x.to_ascii_lowercase())
+}
+
+/// The structural problems of a .seria file's contents, prefixed with `name`
+fn seria_problems(name: &str, bytes: &[u8]) -> Vec<String> {
+    let text = match std::str::from_utf8(bytes)
+    {
+        Ok(text) => text,
+        Err(e) => return vec![format!("{}: not text: {}", name, e)],
+    };
+    let (_, problems) = seria::parse(text);
+    problems.iter().map(|p| format!("{}:{}", name, p)).collect()
+}
+
+/// Checks a downloaded .zip (its central directory, that every entry reads back with a matching
+/// CRC, and that each .seria inside parses) or a single .seria file
+pub fn check_file(path: &Path) -> Result<Check, FlotillaError> {
+    let bytes = std::fs::read(path)
+        .map_err(|e| FlotillaError::Io(format!("Could not read {}: {}", path.display(), e)))?;
+    let sha256 = format!("{:x}", Sha256::digest(&bytes));
+
+    if extension(path).as_deref() == Some("seria")
+    {
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+        return Ok(Check { sha256, entries: 1, ships: 1, problems: seria_problems(name, &bytes) });
+    }
+
+    let mut archive = match zip::ZipArchive::new(std::io::Cursor::new(bytes))
+    {
+        Ok(archive) => archive,
+        Err(e) => return Ok(Check { sha256, entries: 0, ships: 0, problems: vec![format!("Not a readable zip archive: {}", e)] }),
+    };
+    let mut check = Check { sha256, entries: 0, ships: 0, problems: Vec::new() };
+    for index in 0..archive.len()
+    {
+        let mut entry = match archive.by_index(index)
+        {
+            Ok(entry) => entry,
+            Err(e) => {
+                check.problems.push(format!("entry {}: {}", index, e));
+                continue;
+            },
+        };
+        if entry.is_dir()
+        {
+            continue;
+        }
+        check.entries += 1;
+        let name = entry.name().to_string();
+        let mut contents = Vec::new();
+        if let Err(e) = entry.read_to_end(&mut contents)
+        {
+            check.problems.push(format!("{}: {}", name, e));
+            continue;
+        }
+        if extension(Path::new(&name)).as_deref() == Some("seria")
+        {
+            check.ships += 1;
+            check.problems.extend(seria_problems(&name, &contents));
+        }
+    }
+    Ok(check)
+}
+
+/// One recorded download
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Record {
+    pub id: String,
+    pub sha256: String,
+    pub size: u64,
+    pub recorded_at: String,
+}
+
+/// The checksums of everything downloaded into one folder, by file name
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Manifest {
+    pub files: BTreeMap<String, Record>,
+}
+
+impl Manifest {
+    pub fn location(dir: &Path) -> PathBuf {
+        dir.join(MANIFEST)
+    }
+
+    /// The folder's manifest, or an empty one if it has none yet
+    pub fn load(dir: &Path) -> Result<Manifest, FlotillaError> {
+        let path = Self::location(dir);
+        match std::fs::read_to_string(&path)
+        {
+            Ok(contents) => serde_json::from_str(&contents).map_err(|e| FlotillaError::parse(e, &contents).context(path.display().to_string())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Manifest::default()),
+            Err(e) => Err(FlotillaError::Io(format!("Could not read {}: {}", path.display(), e))),
+        }
+    }
+
+    /// Remembers the checksum of a file which was just downloaded and checked
+    pub fn record(&mut self, file: &Path, id: &str, check: &Check) {
+        let name = file.file_name().and_then(|n| n.to_str()).unwrap_or_default().to_string();
+        let size = std::fs::metadata(file).map(|m| m.len()).unwrap_or(0);
+        self.files.insert(name, Record {
+            id: id.to_string(),
+            sha256: check.sha256.clone(),
+            size,
+            recorded_at: Utc::now().to_rfc3339(),
+        });
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<(), FlotillaError> {
+        let path = Self::location(dir);
+        let contents = serde_json::to_string_pretty(self).expect("Application Error: Could not serialize manifest. Please file a bug!");
+        std::fs::write(&path, contents)
+            .map_err(|e| FlotillaError::Io(format!("Could not write {}: {}", path.display(), e)))
+    }
+}
@@ -14,12 +14,24 @@ pub struct Cli {
     pub subcommand: SubCommand,
 
     /// Sets a custom config file
-    #[argp(option, short='c', arg_name="FILE")]
+    #[argp(option, short='c', arg_name="FILE", global)]
     pub config: Option<PathBuf>,
 
-    /// Turn debugging information on
-    #[argp(switch)]
-    pub verbose: Option<bool>,
+    /// Selects a [profile.NAME] section of the config file (default: $FLOTILLA_PROFILE)
+    #[argp(option, arg_name="NAME", global)]
+    pub profile: Option<String>,
+
+    /// Output format: human (default), json, ndjson, yaml or table
+    #[argp(option, short='o', arg_name="FORMAT", global)]
+    pub output: Option<String>,
+
+    /// Log HTTP traffic: -v for requests, status and timing, -vv adds headers and bodies (credentials redacted)
+    #[argp(switch, short='v', global)]
+    pub verbose: u8,
+
+    /// Answer from the metadata cache only, without contacting the server
+    #[argp(switch, global)]
+    pub offline: Option<bool>,
 }
 
 #[derive(FromArgs)]
@@ -32,7 +44,7 @@ pub struct SetupOptions
     #[argp(option, short='u', arg_name = "USERNAME")]
     pub username: Option<String>,
 
-    /// Sets the password
+    /// Sets the password. Prompted for without echo if not given
     #[argp(option, short='p', arg_name = "PASSWORD")]
     pub password: Option<String>,
 
@@ -63,8 +75,77 @@ pub struct LoginOptions
 #[derive(FromArgs)]
 #[derive(Debug, PartialEq)]
 #[argp(subcommand, name = "logout")]
-/// Logs out
+/// Logs out of the current session, or of the one given
 pub struct LogoutOptions
+{
+    /// Log out this user instead of the configured one
+    #[argp(option, short='u', arg_name = "USERNAME")]
+    pub username: Option<String>,
+
+    /// Log out of this API endpoint instead of the configured one
+    #[argp(option, short='e', arg_name = "ENDPOINT")]
+    pub endpoint: Option<String>,
+
+    /// Log out of every stored session
+    #[argp(switch)]
+    pub all: Option<bool>,
+
+    /// Also remove the stored password
+    #[argp(switch)]
+    pub forget: Option<bool>,
+}
+
+#[derive(FromArgs)]
+#[derive(Debug, PartialEq)]
+#[argp(subcommand, name = "whoami")]
+/// Shows who you are logged in as
+pub struct WhoamiOptions
+{
+}
+
+#[derive(FromArgs)]
+#[derive(Debug, PartialEq)]
+#[argp(subcommand, name = "sessions")]
+/// Lists stored sessions and when they expire
+pub struct SessionsOptions
+{
+}
+
+#[derive(FromArgs)]
+#[derive(Debug, PartialEq)]
+#[argp(subcommand, name = "cache")]
+/// Inspects or clears the local metadata cache
+pub struct CacheOptions
+{
+    #[argp(subcommand)]
+    pub action: CacheAction,
+}
+
+#[derive(FromArgs)]
+#[derive(Debug, PartialEq)]
+#[argp(subcommand)]
+pub enum CacheAction
+{
+    /// Removes every cached entry
+    Clear(CacheClearOptions),
+
+    /// Shows how many entries are cached, and how many are still fresh
+    Stats(CacheStatsOptions),
+}
+
+#[derive(FromArgs)]
+#[derive(Debug, PartialEq)]
+#[argp(subcommand, name = "clear")]
+/// Removes every cached entry
+pub struct CacheClearOptions
+{
+}
+
+#[derive(FromArgs)]
+#[derive(Debug, PartialEq)]
+#[argp(subcommand, name = "stats")]
+/// Shows how many entries are cached, and how many are still fresh
+pub struct CacheStatsOptions
 {
 }
 
@@ -79,13 +160,24 @@ pub struct VerifyOptions
     pub file: PathBuf,
 }
 
+#[derive(FromArgs)]
+#[derive(Debug, PartialEq)]
+#[argp(subcommand, name = "check")]
+/// Re-verify downloaded collections and ships against the folder's manifest
+pub struct CheckOptions
+{
+    /// The folder to check (default: the download path)
+    #[argp(positional, arg_name = "DIR")]
+    pub dir: Option<PathBuf>,
+}
+
 #[derive(FromArgs)]
 #[derive(Debug, PartialEq)]
 #[argp(subcommand, name = "get")]
 /// Get a ship or collection by id
 pub struct GetOptions
 {
-    /// The id of the ship or collection to get
+    /// The id, short id or name of the ship or collection to get
     #[argp(positional)]
     pub ids: Vec<String>,
 
@@ -96,6 +188,138 @@ pub struct GetOptions
     /// Try both the public and private API endpoints, yielding two copies, potnetially
     #[argp(switch, short='b')]
     pub both: Option<bool>,
+
+    /// Install the downloaded ships into the ships folder (ships_dir in the config)
+    #[argp(switch, short='x')]
+    pub extract: Option<bool>,
+
+    /// Install the downloaded ships into this folder instead. Implies --extract
+    #[argp(option, arg_name = "DIR")]
+    pub extract_to: Option<PathBuf>,
+
+    /// What to do with a ship of the same name already installed: rename (default), skip or overwrite
+    #[argp(option, arg_name = "POLICY")]
+    pub on_collision: Option<String>,
+
+    /// Save downloads in this folder instead of the download path
+    #[argp(option, short='d', arg_name = "DIR")]
+    pub out_dir: Option<PathBuf>,
+
+    /// File name for downloads, from {name}, {id}, {id8}, {short_id}, {owner} (collections only), {date} (of the download) and {source} (default: {name}-{id8}-{source} for collections, {name} for ships)
+    #[argp(option, arg_name = "TEMPLATE")]
+    pub name_template: Option<String>,
+}
+
+#[derive(FromArgs)]
+#[derive(Debug, PartialEq)]
+#[argp(subcommand, name = "upload")]
+/// Upload one or more .seria ship files
+pub struct UploadOptions
+{
+    /// The .seria files to upload
+    #[argp(positional, arg_name = "FILE")]
+    pub files: Vec<PathBuf>,
+}
+
+#[derive(FromArgs)]
+#[derive(Debug, PartialEq)]
+#[argp(subcommand)]
+/// Create a new object on the server
+pub enum CreateWhat
+{
+    /// Create a new collection
+    Collection(CreateCollectionOptions),
+}
+
+#[derive(FromArgs)]
+#[derive(Debug, PartialEq)]
+#[argp(subcommand, name = "collection")]
+/// Create a new collection from flags or a JSON file. Prints the new collection id
+pub struct CreateCollectionOptions
+{
+    /// Read collection metadata from a JSON file (flags override values in the file)
+    #[argp(option, short='f', arg_name = "FILE")]
+    pub file: Option<PathBuf>,
+
+    /// The name of the collection
+    #[argp(option, short='n', arg_name = "NAME")]
+    pub name: Option<String>,
+
+    /// A description of the collection
+    #[argp(option, short='d', arg_name = "DESCRIPTION")]
+    pub description: Option<String>,
+
+    /// The icon to display for the collection
+    #[argp(option, arg_name = "ICON")]
+    pub icon: Option<String>,
+
+    /// The color to display for the collection
+    #[argp(option, arg_name = "COLOR")]
+    pub color: Option<String>,
+
+    /// Make the collection public
+    #[argp(switch, short='p')]
+    pub public: Option<bool>,
+
+    /// A ship id, short id or name to include in the collection (may be repeated)
+    #[argp(option, short='s', arg_name = "SHIP_ID")]
+    pub ship: Vec<String>,
+}
+
+#[derive(FromArgs)]
+#[derive(Debug, PartialEq)]
+#[argp(subcommand, name = "create")]
+/// Create a new collection
+pub struct CreateOptions
+{
+    /// What to create
+    #[argp(subcommand)]
+    pub what: CreateWhat,
+}
+
+#[derive(FromArgs)]
+#[derive(Debug, PartialEq)]
+#[argp(subcommand, name = "delete")]
+/// Delete one or more ships or collections by id
+pub struct DeleteOptions
+{
+    #[argp(switch, short='y')]
+    /// Do not prompt for confirmation
+    pub yes: Option<bool>,
+
+    /// The ids, short ids or names of the ships or collections to delete
+    #[argp(positional)]
+    pub ids: Vec<String>,
+}
+
+#[derive(FromArgs)]
+#[derive(Debug, PartialEq)]
+#[argp(subcommand, name = "update")]
+/// Replace the .seria file of an existing ship, keeping its id and collections
+pub struct UpdateOptions
+{
+    /// The id, short id or name of the ship to update
+    #[argp(positional, arg_name = "SHIP_ID")]
+    pub id: String,
+
+    /// The new .seria file
+    #[argp(positional, arg_name = "FILE")]
+    pub file: PathBuf,
+}
+
+#[derive(FromArgs)]
+#[derive(Debug, PartialEq)]
+#[argp(subcommand, name = "download")]
+/// Download ships (as .seria files) or collections (as .zip files) by id
+pub struct DownloadOptions
+{
+    /// The ids, short ids or names of the ships or collections to download
+    #[argp(positional)]
+    pub ids: Vec<String>,
+
+    /// Use the public API endpoint instead of the private one
+    #[argp(switch, short='p')]
+    pub public: Option<bool>,
 }
 
 fn parse_list_what(s: &str) -> Result<String, String>
@@ -117,12 +341,48 @@ pub struct ListOptions
     /// What to list? Limit to ships or collections, or omit to list both
     #[argp(positional, from_str_fn(parse_list_what))]
     pub what: Option<String>,
+
+    /// Sort by this column (e.g. downloads, uploaded, name)
+    #[argp(option, short='s', arg_name = "COLUMN")]
+    pub sort: Option<String>,
+
+    /// Reverse the order
+    #[argp(switch, short='r')]
+    pub reverse: Option<bool>,
+
+    /// Comma-separated columns to show. Ships: id, name, downloads, uploaded, collections, full_id, file, url. Collections: name, public, ships, owner, id, description, url
+    #[argp(option, arg_name = "COLUMNS")]
+    pub columns: Option<String>,
+
+    /// Only names matching this glob (e.g. "*frigate*"), or this regex when written as /regex/
+    #[argp(option, arg_name = "PATTERN")]
+    pub name: Option<String>,
+
+    /// Only public collections
+    #[argp(switch)]
+    pub public: Option<bool>,
+
+    /// Only private collections
+    #[argp(switch)]
+    pub private: Option<bool>,
+
+    /// Only ships downloaded at least N times
+    #[argp(option, arg_name = "N")]
+    pub min_downloads: Option<u32>,
+
+    /// Only ships uploaded on or after this date (YYYY-MM-DD)
+    #[argp(option, arg_name = "DATE")]
+    pub uploaded_since: Option<String>,
+
+    /// Only ships in this collection
+    #[argp(option, arg_name = "ID")]
+    pub in_collection: Option<String>,
 }
 
 #[derive(FromArgs)]
 #[derive(Debug, PartialEq)]
 #[argp(subcommand, name = "fetch")]
-/// Fetch a ship or collection by id
+/// Download all of your own ships and collections, skipping those already up to date
 pub struct FetchOptions
 {
 }
@@ -196,7 +456,7 @@ pub struct EditOptions
     /// Do not prompt for confirmation
     pub yes: Option<bool>,
     #[argp(positional)]
-    /// The id of the ship or collection to edit
+    /// The id, short id or name of the ship or collection to edit
     pub id: String,
 
     /// The operation to perform
@@ -214,19 +474,46 @@ pub enum SubCommand
 
     /// Logs in - you must have a valid username and password, visit the Hfopt website to create an account
     Login(LoginOptions),
-    /// Logs out
+    /// Logs out of the current session, or of the one given
     Logout(LogoutOptions),
 
-    /// Pre-verify a .seria file before uploading (not implemented)
+    /// Shows who you are logged in as
+    Whoami(WhoamiOptions),
+
+    /// Lists stored sessions and when they expire
+    Sessions(SessionsOptions),
+
+    /// Inspects or clears the local metadata cache
+    Cache(CacheOptions),
+
+    /// Pre-verify a .seria file before uploading
     Verify(VerifyOptions),
 
+    /// Re-verify downloaded collections and ships
+    Check(CheckOptions),
+
     /// Get a ship or collection by id
     Get(GetOptions),
 
+    /// Download ships or collections by id
+    Download(DownloadOptions),
+
+    /// Upload one or more .seria ship files
+    Upload(UploadOptions),
+
+    /// Create a new collection
+    Create(CreateOptions),
+
+    /// Delete one or more ships or collections by id
+    Delete(DeleteOptions),
+
+    /// Replace the .seria file of an existing ship
+    Update(UpdateOptions),
+
     /// List "ships" "collections" "both" (default=both)
     List(ListOptions),
 
-    /// Fetch a ship or collection by id
+    /// Download all of your own ships and collections
     Fetch(FetchOptions),
 
     /// Edit a ship or collection by id
@@ -0,0 +1,29 @@
+//! Client library for the Highfleete cogitator (HFOPT) at <https://hfopt.jodavaho.io>
+//!
+//! The `flotilla` binary is a thin command line layer over this crate. Other programs can use
+//! [`api::Flotilla`] (async) or [`api::blocking::Flotilla`] directly to list, fetch, edit, upload
+//! and delete ships and collections:
+//!
+//! ```no_run
+//! use flotilla::{api, config::Config};
+//!
+//! let config = Config::new().load_all(None, None, None).unwrap();
+//! let session = api::blocking::login(&config).unwrap();
+//! let user_data = api::blocking::Flotilla::new(config, session).get_user_data().unwrap();
+//! println!("{} ships", user_data.ships.len());
+//! ```
+
+pub mod api;
+pub mod cache;
+pub mod config;
+pub mod credentials;
+pub mod error;
+pub mod install;
+pub mod integrity;
+pub mod models;
+pub mod seria;
+pub mod session;
+
+pub use api::Flotilla;
+pub use error::FlotillaError;
+pub use models::{Collection, HasId, IdType, NewCollection, Ship, UserData};
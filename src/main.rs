@@ -1,33 +1,86 @@
 
-mod api;
-mod config;
 mod interface;
-mod session;
+mod output;
 mod verbs;
 
+use flotilla::config::Config;
+use std::io::IsTerminal;
+use tracing_subscriber::EnvFilter;
 use interface::SubCommand::*;
 use verbs::verify;
+use verbs::check;
 use verbs::setup;
 use verbs::login;
 use verbs::logout;
+use verbs::whoami;
+use verbs::sessions;
+use verbs::cache;
 use verbs::list;
 use verbs::get;
 use verbs::fetch;
 use verbs::edit;
+use verbs::upload;
+use verbs::create;
+use verbs::delete;
+use verbs::update;
+use verbs::download;
 
 fn main() 
 {
-    match argp::parse_args_or_exit::<interface::Cli>(argp::DEFAULT).subcommand
+    let cli = argp::parse_args_or_exit::<interface::Cli>(argp::DEFAULT);
+    init_logging(cli.verbose);
+    match cli.output.as_deref().map(str::parse).unwrap_or(Ok(output::Format::Human))
+    {
+        Ok(format) => output::set_format(format),
+        Err(e) => {
+            output::emit_error(&e);
+            std::process::exit(e.exit_code());
+        },
+    }
+    let config = Config::new()
+        .with_file(cli.config)
+        .with_profile(cli.profile)
+        .with_offline(cli.offline.unwrap_or(false));
+    match cli.subcommand
     {
         Verify(options) => verify::exec(options.file),
-        Setup(options) => setup::exec(options.username, options.password, options.endpoint),
-        Login(options) => login::exec(options.username, options.password, options.endpoint),
-        Logout(_) => logout::exec(),
-        Get(options) => get::exec(options.ids, options.both, options.public),
-        List(options) => list::exec(options.what),
-        Fetch(_) => fetch::exec(),
-        Edit(options) => edit::exec(options.id, options.operation, options.yes),
+        Check(options) => check::exec(config, options.dir),
+        Setup(options) => setup::exec(config, options.username, options.password, options.endpoint),
+        Login(options) => login::exec(config, options.username, options.password, options.endpoint),
+        Logout(options) => logout::exec(config, options.username, options.endpoint, options.all, options.forget),
+        Whoami(_) => whoami::exec(config),
+        Sessions(_) => sessions::exec(config),
+        Cache(options) => cache::exec(config, options.action),
+        Get(options) => get::exec(config, options),
+        Download(options) => download::exec(config, options.ids, options.public),
+        Upload(options) => upload::exec(config, options.files),
+        Create(options) => create::exec(config, options.what),
+        Delete(options) => delete::exec(config, options.ids, options.yes),
+        Update(options) => update::exec(config, options.id, options.file),
+        List(options) => list::exec(config, options),
+        Fetch(_) => fetch::exec(config),
+        Edit(options) => edit::exec(config, options.id, options.operation, options.yes),
     }
-    .unwrap_or_else(|e| eprintln!("Errors encountered:\n{}", e));
+    .unwrap_or_else(|e| {
+        output::emit_error(&e);
+        std::process::exit(e.exit_code());
+    });
 
 }
+
+/// Logs to stderr at the level chosen by `-v`/`-vv`, unless `FLOTILLA_LOG` (e.g. "flotilla=trace") says otherwise
+fn init_logging(verbosity: u8)
+{
+    let level = match verbosity {
+        0 => "flotilla=warn",
+        1 => "flotilla=debug",
+        _ => "flotilla=trace",
+    };
+    let filter = EnvFilter::try_from_env("FLOTILLA_LOG").unwrap_or_else(|_| EnvFilter::new(level));
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .with_ansi(std::io::stderr().is_terminal())
+        .without_time()
+        .init();
+}
@@ -0,0 +1,280 @@
+// Purpose: Data types exchanged with the HFOPT API
+
+use crate::error::FlotillaError;
+
+#[derive(Debug)]
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct UserData
+{
+    pub ships: Vec<Ship>,
+    pub collections: Vec<Collection>,
+}
+
+/// Anything on the server which is addressed by an id
+pub trait HasId
+{
+    fn get_id(&self) -> String;
+}
+
+#[derive(Debug)]
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct Ship {
+    pub id: String,
+    #[serde(rename = "shipName")]
+    pub name: String,
+    #[serde(rename = "fileName")]
+    pub file_name: String,
+    #[serde(rename = "shortId")]
+    pub short_id: String,
+    pub downloads: u32,
+    pub uploaded: u64,
+    #[serde(rename = "numCollections")]
+    pub num_collections: u32,
+    #[serde(rename = "downloadUrl")]
+    pub download_url: String,
+}
+impl Ship
+{
+    /// When this ship was uploaded. The server has sent both seconds and milliseconds since the epoch
+    pub fn uploaded_at(&self) -> Option<chrono::DateTime<chrono::Utc>>
+    {
+        match self.uploaded
+        {
+            0 => None,
+            t if t > 100_000_000_000 => chrono::DateTime::from_timestamp((t / 1000) as i64, 0),
+            t => chrono::DateTime::from_timestamp(t as i64, 0),
+        }
+    }
+}
+impl HasId for Ship
+{
+    fn get_id(&self) -> String
+    {
+        self.id.clone()
+    }
+}
+
+#[derive(Debug)]
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct Collection {
+    pub id: String,
+    #[serde(rename = "collectionName")]
+    pub name: String,
+    pub description: String,
+    #[serde(rename = "publicUrl")]
+    pub public_url: String,
+    #[serde(rename = "ships")]
+    pub ship_ids: Vec<String>,
+    pub icon: String,
+    pub color: String,
+    #[serde(rename = "isPublic")]
+    pub is_public: bool,
+    #[serde(rename = "downloadUrl")]
+    pub download_url: String,
+    #[serde(rename = "collectionOwner")]
+    pub owner: String,
+}
+impl HasId for Collection
+{
+    fn get_id(&self) -> String
+    {
+        self.id.clone()
+    }
+}
+
+#[derive(Debug, Default)]
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct NewCollection {
+    #[serde(rename = "collectionName")]
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub icon: String,
+    #[serde(default)]
+    pub color: String,
+    #[serde(rename = "isPublic", default)]
+    pub is_public: bool,
+    #[serde(rename = "ships", default)]
+    pub ship_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IdType{
+    Collection,
+    Ship,
+}
+
+pub fn get_id_type(id: &str) -> Result<IdType, FlotillaError>{
+    match id.len()
+    {
+        32 => Ok(IdType::Collection),
+        64 => Ok(IdType::Ship),
+        _ => Err(FlotillaError::InvalidId(id.to_string())),
+    }
+}
+
+/// Whether this is a full id, which can be used without looking it up
+pub fn is_full_id(id: &str) -> bool
+{
+    get_id_type(id).is_ok() && id.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+/// A ship or collection which an id or name might refer to
+struct Candidate<'a> {
+    id: &'a str,
+    kind: IdType,
+    short_id: Option<&'a str>,
+    name: &'a str,
+}
+
+impl Candidate<'_> {
+    fn describe(&self) -> String
+    {
+        match self.kind
+        {
+            IdType::Ship => format!("ship {} '{}'", self.short_id.unwrap_or(self.id), self.name),
+            IdType::Collection => format!("collection {} '{}'", self.id, self.name),
+        }
+    }
+}
+
+/// Finds the one ship or collection meant by `query`: a full id, a short id, an exact name or a
+/// unique prefix of either id. With `kind`, only objects of that kind are considered.
+///
+/// Exact matches win over prefixes. Several matches are an [`FlotillaError::Ambiguous`] error.
+pub fn resolve_id(user_data: &UserData, query: &str, kind: Option<IdType>) -> Result<(String, IdType), FlotillaError>
+{
+    if query.is_empty()
+    {
+        return Err(FlotillaError::InvalidId(query.to_string()));
+    }
+    let ships = user_data.ships.iter().map(|s| Candidate {
+        id: &s.id,
+        kind: IdType::Ship,
+        short_id: Some(&s.short_id),
+        name: &s.name,
+    });
+    let collections = user_data.collections.iter().map(|c| Candidate {
+        id: &c.id,
+        kind: IdType::Collection,
+        short_id: None,
+        name: &c.name,
+    });
+    let candidates: Vec<Candidate> = ships
+        .chain(collections)
+        .filter(|c| kind.is_none_or(|k| c.kind == k))
+        .collect();
+
+    let exact: Vec<&Candidate> = candidates
+        .iter()
+        .filter(|c| c.id == query || c.short_id == Some(query) || c.name == query)
+        .collect();
+    let matches = match exact.is_empty()
+    {
+        false => exact,
+        true => candidates
+            .iter()
+            .filter(|c| c.id.starts_with(query) || c.short_id.is_some_and(|s| s.starts_with(query)))
+            .collect(),
+    };
+
+    match matches.as_slice()
+    {
+        [] => {
+            let what = match kind {
+                Some(IdType::Ship) => "ship",
+                Some(IdType::Collection) => "collection",
+                None => "ship or collection",
+            };
+            Err(FlotillaError::NotFound(format!("{} '{}'", what, query)))
+        },
+        [found] => Ok((found.id.to_string(), found.kind)),
+        _ => Err(FlotillaError::Ambiguous {
+            query: query.to_string(),
+            candidates: matches.iter().map(|c| c.describe()).collect(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ship(id: &str, short_id: &str, name: &str) -> Ship {
+        Ship {
+            id: id.to_string(),
+            name: name.to_string(),
+            file_name: format!("{}.seria", name),
+            short_id: short_id.to_string(),
+            downloads: 0,
+            uploaded: 0,
+            num_collections: 0,
+            download_url: String::new(),
+        }
+    }
+
+    fn collection(id: &str, name: &str) -> Collection {
+        Collection {
+            id: id.to_string(),
+            name: name.to_string(),
+            description: String::new(),
+            public_url: String::new(),
+            ship_ids: vec![],
+            icon: String::new(),
+            color: String::new(),
+            is_public: false,
+            download_url: String::new(),
+            owner: "bob".to_string(),
+        }
+    }
+
+    fn user_data() -> UserData {
+        UserData {
+            ships: vec![
+                ship(&format!("ab12{}", "0".repeat(60)), "ab1200", "Zeta"),
+                ship(&format!("ab34{}", "1".repeat(60)), "ab3411", "Kestrel"),
+            ],
+            collections: vec![collection(&format!("cd{}", "2".repeat(30)), "Fleet")],
+        }
+    }
+
+    #[test]
+    fn exact_ids_resolve() {
+        let data = user_data();
+        let ship_id = format!("ab12{}", "0".repeat(60));
+        assert_eq!(resolve_id(&data, &ship_id, None).unwrap(), (ship_id.clone(), IdType::Ship));
+        assert_eq!(resolve_id(&data, "ab3411", None).unwrap().0, format!("ab34{}", "1".repeat(60)));
+        let collection_id = format!("cd{}", "2".repeat(30));
+        assert_eq!(resolve_id(&data, &collection_id, None).unwrap(), (collection_id, IdType::Collection));
+    }
+
+    #[test]
+    fn unique_prefix_resolves() {
+        let data = user_data();
+        assert_eq!(resolve_id(&data, "ab12", None).unwrap().0, format!("ab12{}", "0".repeat(60)));
+        assert_eq!(resolve_id(&data, "cd", None).unwrap().1, IdType::Collection);
+    }
+
+    #[test]
+    fn ambiguous_prefix_lists_candidates() {
+        match resolve_id(&user_data(), "ab", None)
+        {
+            Err(FlotillaError::Ambiguous { query, candidates }) => {
+                assert_eq!(query, "ab");
+                assert_eq!(candidates, vec!["ship ab1200 'Zeta'", "ship ab3411 'Kestrel'"]);
+            },
+            other => panic!("expected an ambiguous id, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn names_resolve_and_kind_filters() {
+        let data = user_data();
+        assert_eq!(resolve_id(&data, "Kestrel", None).unwrap().0, format!("ab34{}", "1".repeat(60)));
+        assert_eq!(resolve_id(&data, "Fleet", Some(IdType::Collection)).unwrap().1, IdType::Collection);
+        assert!(matches!(resolve_id(&data, "Fleet", Some(IdType::Ship)), Err(FlotillaError::NotFound(_))));
+        assert!(matches!(resolve_id(&data, "zz", None), Err(FlotillaError::NotFound(_))));
+        assert!(matches!(resolve_id(&data, "", None), Err(FlotillaError::InvalidId(_))));
+    }
+}
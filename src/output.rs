@@ -0,0 +1,166 @@
+// Purpose: Prints verb results in the format chosen with --output
+
+use flotilla::FlotillaError;
+use indicatif::{MultiProgress, ProgressDrawTarget};
+use serde::Serialize;
+use serde_json::Value;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// Whatever each verb prints for people: progress bars, diffs, pretty JSON
+    Human,
+    Json,
+    /// One JSON document per line; arrays are printed one element per line
+    Ndjson,
+    Yaml,
+    Table,
+}
+
+impl FromStr for Format {
+    type Err = FlotillaError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(Format::Human),
+            "json" => Ok(Format::Json),
+            "ndjson" => Ok(Format::Ndjson),
+            "yaml" => Ok(Format::Yaml),
+            "table" => Ok(Format::Table),
+            _ => Err(FlotillaError::Usage(format!("Unknown output format '{}'. Please use human, json, ndjson, yaml or table", s))),
+        }
+    }
+}
+
+static FORMAT: OnceLock<Format> = OnceLock::new();
+
+/// Chooses the output format for the rest of the run. Only the first call has any effect
+pub fn set_format(format: Format)
+{
+    FORMAT.get_or_init(|| format);
+}
+
+pub fn format() -> Format
+{
+    *FORMAT.get().unwrap_or(&Format::Human)
+}
+
+pub fn is_human() -> bool
+{
+    format() == Format::Human
+}
+
+/// Progress bars, which are only drawn for people
+pub fn progress() -> MultiProgress
+{
+    match is_human() {
+        true => MultiProgress::new(),
+        false => MultiProgress::with_draw_target(ProgressDrawTarget::hidden()),
+    }
+}
+
+/// Prints a verb's result on stdout: by calling `human` in the human format, else by serializing `value`
+pub fn emit<T: Serialize>(value: &T, human: impl FnOnce())
+{
+    if is_human()
+    {
+        human();
+        return;
+    }
+    let value = serde_json::to_value(value).expect("Application Error: Could not serialize output. Please file a bug!");
+    print!("{}", render(&value));
+}
+
+/// Prints an error on stderr, as a JSON (or YAML) object unless the format is for people
+pub fn emit_error(e: &FlotillaError)
+{
+    match format() {
+        Format::Human | Format::Table => eprintln!("Errors encountered:\n{}", e),
+        _ => eprint!("{}", render(&serde_json::json!({ "error": e.to_json() }))),
+    }
+}
+
+fn render(value: &Value) -> String
+{
+    match format() {
+        Format::Human | Format::Json => format!("{}\n", serde_json::to_string_pretty(value).unwrap()),
+        Format::Ndjson => match value {
+            Value::Array(items) => items.iter().map(|item| format!("{}\n", item)).collect(),
+            _ => format!("{}\n", value),
+        },
+        Format::Yaml => serde_yaml::to_string(value).expect("Application Error: Could not serialize output. Please file a bug!"),
+        Format::Table => table(value),
+    }
+}
+
+/// How a value looks inside a table cell
+pub fn cell(value: &Value) -> String
+{
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        Value::Array(items) if items.iter().all(|i| !i.is_array() && !i.is_object()) => {
+            items.iter().map(cell).collect::<Vec<String>>().join(",")
+        },
+        _ => value.to_string(),
+    }
+}
+
+/// Lines up rows of cells under a header
+pub fn format_table(header: &[String], rows: &[Vec<String>]) -> String
+{
+    let mut widths: Vec<usize> = header.iter().map(|h| h.chars().count()).collect();
+    for row in rows.iter()
+    {
+        for (width, cell) in widths.iter_mut().zip(row.iter())
+        {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let line = |cells: &[String]| -> String {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(widths.iter())
+            .map(|(c, w)| format!("{:<width$}", c, width = *w))
+            .collect();
+        format!("{}\n", padded.join("  ").trim_end())
+    };
+    let mut out = line(header);
+    out.push_str(&line(&widths.iter().map(|w| "-".repeat(*w)).collect::<Vec<String>>()));
+    rows.iter().for_each(|row| out.push_str(&line(row)));
+    out
+}
+
+/// Arrays of objects become one row per element; anything else a table of keys and values
+fn table(value: &Value) -> String
+{
+    match value {
+        Value::Array(items) if items.iter().all(|i| i.is_object()) => {
+            let mut header: Vec<String> = Vec::new();
+            for item in items.iter()
+            {
+                for key in item.as_object().unwrap().keys()
+                {
+                    if !header.contains(key)
+                    {
+                        header.push(key.clone());
+                    }
+                }
+            }
+            let rows: Vec<Vec<String>> = items
+                .iter()
+                .map(|item| header.iter().map(|key| cell(&item[key])).collect())
+                .collect();
+            format_table(&header, &rows)
+        },
+        Value::Object(map) => {
+            let rows: Vec<Vec<String>> = map
+                .iter()
+                .map(|(key, value)| vec![key.clone(), cell(value)])
+                .collect();
+            format_table(&["key".to_string(), "value".to_string()], &rows)
+        },
+        _ => format!("{}\n", cell(value)),
+    }
+}
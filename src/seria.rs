@@ -0,0 +1,315 @@
+// Purpose: Offline parsing and validation of HighFleet .seria ship files
+//
+// A .seria file is a tree of nodes. Each node is opened by a line holding only `{`
+// and closed by a line holding only `}`. Inside a node every other line is a
+// `key=value` field; nested nodes are children of the node that encloses them.
+// The outermost node is the ship itself and carries the ship header.
+
+use std::fmt;
+
+/// Keys which must be present on the outermost (ship) node
+pub const REQUIRED_HEADER_KEYS: [&str; 3] = ["m_classname", "m_name", "m_version"];
+
+/// Component classes the shipyard knows how to handle. The game may know more, so any other
+/// class is only a warning
+pub const KNOWN_CLASSES: [&str; 16] = [
+    "Ship",
+    "Frame",
+    "Hull",
+    "Armor",
+    "Item",
+    "Engine",
+    "Gun",
+    "Turret",
+    "Launcher",
+    "Reactor",
+    "Tank",
+    "Radar",
+    "Cabin",
+    "Storage",
+    "Light",
+    "Decor",
+];
+
+/// Keys whose values must be one or more whitespace separated numbers
+pub const NUMERIC_KEYS: [&str; 9] = [
+    "m_version",
+    "m_index",
+    "m_pos",
+    "m_angle",
+    "m_scale",
+    "m_mass",
+    "m_hp",
+    "m_armor",
+    "m_cost",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// The file is not a valid ship
+    Error,
+    /// Worth a look, but does not make the file invalid
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct Problem {
+    pub line: usize,
+    pub column: usize,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Problem {
+    pub fn error(line: usize, column: usize, message: String) -> Problem {
+        Problem { line, column, severity: Severity::Error, message }
+    }
+
+    pub fn warning(line: usize, column: usize, message: String) -> Problem {
+        Problem { line, column, severity: Severity::Warning, message }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.severity
+        {
+            Severity::Error => write!(f, "{}:{}: {}", self.line, self.column, self.message),
+            Severity::Warning => write!(f, "{}:{}: warning: {}", self.line, self.column, self.message),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Field {
+    pub line: usize,
+    pub key: String,
+    pub value: String,
+    pub value_column: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Node {
+    pub line: usize,
+    pub column: usize,
+    pub fields: Vec<Field>,
+    pub children: Vec<Node>,
+}
+
+impl Node {
+    pub fn get(&self, key: &str) -> Option<&Field> {
+        self.fields.iter().find(|f| f.key == key)
+    }
+}
+
+/// Parses the text of a .seria file into its root node, collecting every structural problem found
+pub fn parse(text: &str) -> (Option<Node>, Vec<Problem>)
+{
+    let mut problems = Vec::new();
+    let mut stack: Vec<Node> = Vec::new();
+    let mut roots: Vec<Node> = Vec::new();
+    let mut last_line = 0;
+
+    for (idx, raw) in text.lines().enumerate()
+    {
+        let line = idx + 1;
+        last_line = line;
+        let trimmed = raw.trim();
+        if trimmed.is_empty()
+        {
+            continue;
+        }
+        let column = raw.len() - raw.trim_start().len() + 1;
+
+        match trimmed
+        {
+            "{" => {
+                stack.push(Node { line, column, ..Default::default() });
+            },
+            "}" => {
+                match stack.pop()
+                {
+                    Some(node) => match stack.last_mut()
+                    {
+                        Some(parent) => parent.children.push(node),
+                        None => roots.push(node),
+                    },
+                    None => problems.push(Problem::error(line, column, "Unmatched '}' closes a node that was never opened".to_string())),
+                }
+            },
+            _ => {
+                let Some(node) = stack.last_mut() else {
+                    problems.push(Problem::error(line, column, format!("Field '{}' appears outside of any node", trimmed)));
+                    continue;
+                };
+                match trimmed.split_once('=')
+                {
+                    Some((key, value)) => {
+                        let key = key.trim_end();
+                        if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+                        {
+                            problems.push(Problem::error(line, column, format!("Invalid key '{}'", key)));
+                            continue;
+                        }
+                        let value_offset = trimmed.find('=').unwrap() + 1;
+                        let value_column = column + value_offset + (value.len() - value.trim_start().len());
+                        node.fields.push(Field {
+                            line,
+                            key: key.to_string(),
+                            value: value.trim().to_string(),
+                            value_column,
+                        });
+                    },
+                    None => problems.push(Problem::error(line, column, format!("Expected key=value, found '{}'", trimmed))),
+                }
+            },
+        }
+    }
+
+    // Close anything left open so the remaining checks still see the whole tree
+    while let Some(node) = stack.pop()
+    {
+        problems.push(Problem::error(node.line, node.column, format!("Node opened here is never closed (reached end of file at line {})", last_line)));
+        match stack.last_mut()
+        {
+            Some(parent) => parent.children.push(node),
+            None => roots.push(node),
+        }
+    }
+
+    if roots.len() > 1
+    {
+        for extra in roots.iter().skip(1)
+        {
+            problems.push(Problem::error(extra.line, extra.column, "Only one top-level ship node is allowed".to_string()));
+        }
+    }
+
+    if roots.is_empty()
+    {
+        problems.push(Problem::error(1, 1, "File contains no ship node".to_string()));
+    }
+
+    (roots.into_iter().next(), problems)
+}
+
+/// Parses and checks the contents of a .seria file, returning every problem found in file order.
+/// The file is valid if none of them is an error
+pub fn validate(text: &str) -> Vec<Problem>
+{
+    let (root, mut problems) = parse(text);
+
+    if let Some(root) = root
+    {
+        for key in REQUIRED_HEADER_KEYS.iter()
+        {
+            if root.get(key).is_none()
+            {
+                problems.push(Problem::error(root.line, root.column, format!("Ship header is missing required key '{}'", key)));
+            }
+        }
+        check_node(&root, &mut problems);
+    }
+
+    problems.sort_by_key(|p| (p.line, p.column));
+    problems
+}
+
+fn check_node(node: &Node, problems: &mut Vec<Problem>)
+{
+    for field in node.fields.iter()
+    {
+        if field.key == "m_classname" && !KNOWN_CLASSES.contains(&field.value.as_str())
+        {
+            problems.push(Problem::warning(field.line, field.value_column, format!("Unknown component '{}'", field.value)));
+        }
+        if NUMERIC_KEYS.contains(&field.key.as_str())
+        {
+            if field.value.is_empty()
+            {
+                problems.push(Problem::error(field.line, field.value_column, format!("'{}' must be numeric but is empty", field.key)));
+            }
+            let mut offset = 0;
+            for token in field.value.split_whitespace()
+            {
+                let at = field.value[offset..].find(token).unwrap() + offset;
+                offset = at + token.len();
+                if token.parse::<f64>().is_err()
+                {
+                    problems.push(Problem::error(field.line, field.value_column + at, format!("'{}' must be numeric, found '{}'", field.key, token)));
+                }
+            }
+        }
+    }
+
+    for child in node.children.iter()
+    {
+        check_node(child, problems);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VALID: &str = "{\nm_classname=Ship\nm_name=Tester\nm_version=3\n  {\n  m_classname=Engine\n  m_pos=1.5 -2 0\n  }\n}\n";
+
+    fn errors(text: &str) -> Vec<Problem> {
+        validate(text).into_iter().filter(|p| p.is_error()).collect()
+    }
+
+    #[test]
+    fn valid_file_has_no_problems() {
+        assert_eq!(validate(VALID), vec![]);
+        let (root, problems) = parse(VALID);
+        let root = root.unwrap();
+        assert!(problems.is_empty());
+        assert_eq!(root.get("m_name").unwrap().value, "Tester");
+        assert_eq!(root.children.len(), 1);
+    }
+
+    #[test]
+    fn unclosed_node_is_reported_where_it_opens() {
+        let problems = errors("{\nm_classname=Ship\nm_name=T\nm_version=1\n  {\n  m_classname=Gun\n}\n");
+        assert_eq!(problems.len(), 1);
+        assert_eq!((problems[0].line, problems[0].column), (1, 1));
+        assert!(problems[0].message.contains("never closed"));
+    }
+
+    #[test]
+    fn unmatched_close_is_reported() {
+        let problems = errors(&format!("{}}}\n", VALID));
+        assert_eq!(problems.len(), 1);
+        assert_eq!((problems[0].line, problems[0].column), (10, 1));
+        assert!(problems[0].message.contains("Unmatched"));
+    }
+
+    #[test]
+    fn missing_header_key_is_reported() {
+        let problems = errors("{\nm_classname=Ship\nm_name=T\n}\n");
+        assert_eq!(problems.len(), 1);
+        assert_eq!((problems[0].line, problems[0].column), (1, 1));
+        assert!(problems[0].message.contains("'m_version'"));
+    }
+
+    #[test]
+    fn bad_numeric_token_points_at_the_token() {
+        let problems = errors("{\nm_classname=Ship\nm_name=T\nm_version=1\n  m_pos=1 abc 3\n}\n");
+        assert_eq!(problems.len(), 1);
+        assert_eq!((problems[0].line, problems[0].column), (5, 11));
+        assert!(problems[0].message.contains("'abc'"));
+    }
+
+    #[test]
+    fn unknown_component_is_only_a_warning() {
+        let problems = validate("{\nm_classname=Ship\nm_name=T\nm_version=1\n  {\n  m_classname=Shield\n  }\n}\n");
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].severity, Severity::Warning);
+        assert_eq!((problems[0].line, problems[0].column), (6, 15));
+    }
+}
@@ -1,13 +1,55 @@
+use crate::config::Config;
+use crate::error::FlotillaError;
 use chrono::Utc;
 use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 
-#[derive(Serialize,Deserialize,Debug)]
+#[derive(Serialize,Deserialize,Debug,Clone)]
 pub struct Session {
     pub id_token: String,
     pub user_id: String,
     pub refresh_token: String,
     pub expiration_unix: i64,
+    /// The API this session was issued by. Sessions are stored per endpoint and user
+    #[serde(default)]
+    pub endpoint: String,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn location() -> PathBuf {
+    let config_dir = ProjectDirs::from("io", "Jodavaho", "Flotilla").expect("Application Error: Could not load configuration directory. Please file a bug!");
+    config_dir.config_dir().join("sessions.json")
+}
+
+/// Sessions written by older versions, which kept only one
+fn legacy_location() -> PathBuf {
+    let config_dir = ProjectDirs::from("io", "Jodavaho", "Flotilla").expect("Application Error: Could not load configuration directory. Please file a bug!");
+    config_dir.config_dir().join("session.json")
+}
+
+fn save_all(sessions: &[Session]) -> Result<(), FlotillaError> {
+    let config_file = location();
+    let config_dir = config_file.parent().expect("Application Error: Could not load configuration directory. Please file a bug!");
+    //make sure the config directory exists
+    std::fs::create_dir_all(config_dir)
+        .map_err(|e| FlotillaError::ConfigIo(format!("Could not create {}: {}", config_dir.display(), e)))?;
+    let file = std::fs::File::create(&config_file)
+        .map_err(|e| FlotillaError::ConfigIo(format!("Could not create {}: {}", config_file.display(), e)))?;
+    // The tokens are as good as a password until they expire, so restrict them before writing any
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&config_file, std::fs::Permissions::from_mode(0o600))
+            .map_err(|e| FlotillaError::ConfigIo(format!("Could not restrict permissions of {}: {}", config_file.display(), e)))?;
+    }
+    serde_json::to_writer_pretty(&file, sessions)
+        .map_err(|e| FlotillaError::ConfigIo(format!("Could not write {}: {}", config_file.display(), e)))
 }
 
 impl Session{
@@ -18,62 +60,96 @@ impl Session{
             user_id: String::from(""),
             refresh_token: String::from(""),
             expiration_unix: Utc::now().timestamp()-1,
+            endpoint: String::from(""),
         }
     }
 
     pub fn expired(&self) -> bool {
         let now = Utc::now().timestamp();
-        if now >= self.expiration_unix {
-            return true;
-        }
-        return false;
+        now >= self.expiration_unix
     }
-    pub fn load_all(&self) -> Self {
-        let config_dir = ProjectDirs::from("io", "Jodavaho", "Flotilla").expect("Application Error: Could not load configuration directory. Please file a bug!");
-        let config_file = config_dir.config_dir().join("session.json");
 
-        //load raw config file contents into a string for serde
-        let contents = match std::fs::read_to_string(&config_file)
-        {
-            Ok(contents) => contents,
-            Err(_) => {
-                String::from("")
-            }
-        };
+    fn is_for(&self, endpoint: &str, user_id: &str) -> bool {
+        self.endpoint == endpoint && self.user_id == user_id
+    }
 
-        if let Ok(found) = serde_json::from_str(&contents)
-        {
-            return found;
-        }
+    /// Sessions from older versions do not know their endpoint; they are taken to be for whichever
+    /// endpoint the user asks for first
+    fn is_legacy_for(&self, user_id: &str) -> bool {
+        self.endpoint.is_empty() && self.user_id == user_id
+    }
 
-        Session{
-            id_token: self.id_token.clone(),
-            user_id: self.user_id.clone(),
-            refresh_token: self.refresh_token.clone(),
-            expiration_unix: self.expiration_unix.clone(),
+    /// Every stored session, for any endpoint and user. The single session kept by older versions
+    /// is moved into the store the first time it is loaded
+    pub fn load_stored() -> Vec<Session> {
+        let contents = std::fs::read_to_string(location()).unwrap_or_default();
+        let mut sessions: Vec<Session> = serde_json::from_str(&contents).unwrap_or_default();
+        let legacy = std::fs::read_to_string(legacy_location())
+            .ok()
+            .and_then(|contents| serde_json::from_str::<Session>(&contents).ok());
+        if let Some(legacy) = legacy
+        {
+            if !sessions.iter().any(|s| s.user_id == legacy.user_id)
+            {
+                sessions.push(legacy);
+            }
+            // Kept until the store is written, so nothing is lost if that fails
+            if save_all(&sessions).is_ok()
+            {
+                let _ = std::fs::remove_file(legacy_location());
+            }
         }
+        sessions
+    }
 
+    /// The stored session for the configured endpoint and user, or an expired one if there is none
+    pub fn load_for(config: &Config) -> Session {
+        let sessions = Self::load_stored();
+        sessions.iter()
+            .find(|s| s.is_for(&config.endpoint, &config.username))
+            .or_else(|| sessions.iter().find(|s| s.is_legacy_for(&config.username)))
+            .map(|s| Session { endpoint: config.endpoint.clone(), ..s.clone() })
+            .unwrap_or_else(|| Session {
+                user_id: config.username.clone(),
+                endpoint: config.endpoint.clone(),
+                ..Session::new()
+            })
+    }
 
+    /// Removes this session from the store
+    pub fn remove(self) -> Result<Self, FlotillaError> {
+        let mut sessions = Self::load_stored();
+        let count = sessions.len();
+        sessions.retain(|s| !s.is_for(&self.endpoint, &self.user_id) && !s.is_legacy_for(&self.user_id));
+        if sessions.len() == count
+        {
+            return Err(FlotillaError::NotFound(format!("session for {} at {}", self.user_id, self.endpoint)));
+        }
+        save_all(&sessions)?;
+        Ok(self)
     }
 
-    pub fn remove(self) -> Result<Self, String> {
-        let config_dir = ProjectDirs::from("io", "Jodavaho", "Flotilla").expect("Application Error: Could not load configuration directory. Please file a bug!");
-        let config_file = config_dir.config_dir().join("session.json");
-        match std::fs::remove_file(&config_file)
+    /// Removes every stored session
+    pub fn remove_all() -> Result<(), FlotillaError> {
+        for config_file in [location(), legacy_location()]
         {
-            Ok(_) => Ok(self),
-            Err(x) => Err(x.to_string()),
+            match std::fs::remove_file(&config_file)
+            {
+                Err(x) if x.kind() != std::io::ErrorKind::NotFound => {
+                    return Err(FlotillaError::ConfigIo(format!("Could not remove {}: {}", config_file.display(), x)));
+                },
+                _ => {},
+            }
         }
+        Ok(())
     }
 
-    pub fn save_to_default(&self) -> &Self{
-        let config_dir = ProjectDirs::from("io", "Jodavaho", "Flotilla").expect("Application Error: Could not load configuration directory. Please file a bug!");
-        let config_file = config_dir.config_dir().join("session.json");
-        //make sure the config directory exists
-        std::fs::create_dir_all(config_dir.config_dir()).expect("Application Error: Could not create configuration directory. Please file a bug!");
-        let file = std::fs::File::create(&config_file).expect("Failed to create config file");
-        serde_json::to_writer_pretty(&file, self).expect("Failed to write config file");
-        self
+    /// Stores this session, replacing any other for the same endpoint and user
+    pub fn save_to_default(&self) -> Result<&Self, FlotillaError>{
+        let mut sessions = Self::load_stored();
+        sessions.retain(|s| !s.is_for(&self.endpoint, &self.user_id) && !s.is_legacy_for(&self.user_id));
+        sessions.push(self.clone());
+        save_all(&sessions)?;
+        Ok(self)
     }
 }
-
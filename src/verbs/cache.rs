@@ -0,0 +1,32 @@
+use crate::interface::CacheAction;
+use flotilla::cache;
+use flotilla::config::Config;
+use flotilla::FlotillaError;
+use crate::output;
+use serde_json::json;
+
+pub fn exec(config: Config, action: CacheAction) -> Result<(), FlotillaError>
+{
+    match action
+    {
+        CacheAction::Clear(_) => {
+            let removed = cache::clear()?;
+            let location = cache::location().display().to_string();
+            output::emit(&json!({ "location": location, "removed": removed }), || {
+                println!("Removed {} cached entries from {}", removed, location);
+            });
+        },
+        CacheAction::Stats(_) => {
+            // The TTL decides what counts as fresh, but the cache can be inspected without a config
+            let ttl = config.clone().load_all(None,None,None).unwrap_or_else(|_| config.load_env()).cache_ttl;
+            let stats = cache::stats(ttl);
+            output::emit(&stats, || {
+                println!("Location: {}", stats.location);
+                println!("Entries:  {} ({} bytes)", stats.entries, stats.bytes);
+                println!("Fresh:    {} (TTL {} seconds)", stats.fresh, ttl);
+                println!("Stale:    {}", stats.stale);
+            });
+        },
+    }
+    Ok(())
+}
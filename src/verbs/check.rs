@@ -0,0 +1,78 @@
+use flotilla::config::Config;
+use flotilla::integrity::{self, Manifest};
+use flotilla::FlotillaError;
+use crate::output;
+use serde_json::json;
+use std::path::PathBuf;
+
+pub fn exec(config: Config, dir: Option<PathBuf>) -> Result<(), FlotillaError>
+{
+    let dir = match dir
+    {
+        Some(dir) => dir,
+        None => {
+            // Checking does not need an account, so a missing config file just means the default folder
+            PathBuf::from(config.clone().load_all(None,None,None).unwrap_or_else(|_| config.load_env()).download_path)
+        },
+    };
+    let manifest = Manifest::load(&dir)?;
+    let mut files: Vec<PathBuf> = std::fs::read_dir(&dir)
+        .map_err(|e| FlotillaError::Io(format!("Could not read {}: {}", dir.display(), e)))?
+        .filter_map(|f| f.ok())
+        .map(|f| f.path())
+        .filter(|p| p.is_file() && integrity::is_checkable(p))
+        .collect();
+    files.sort();
+
+    let mut report = Vec::new();
+    for path in files.iter()
+    {
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default().to_string();
+        let recorded = manifest.files.get(&name);
+        let (status, sha256, problems) = match integrity::check_file(path)
+        {
+            Err(e) => ("invalid", None, vec![e.to_string()]),
+            Ok(check) if !check.is_valid() => ("invalid", Some(check.sha256), check.problems),
+            Ok(check) => match recorded
+            {
+                None => ("unrecorded", Some(check.sha256), vec![]),
+                Some(record) if record.sha256 == check.sha256 => ("ok", Some(check.sha256), vec![]),
+                Some(record) => {
+                    let problem = format!("Checksum differs from the one recorded on {}", record.recorded_at);
+                    ("changed", Some(check.sha256), vec![problem])
+                },
+            },
+        };
+        report.push(json!({ "file": name, "status": status, "sha256": sha256, "problems": problems }));
+    }
+    for (name, record) in manifest.files.iter()
+    {
+        if !dir.join(name).exists()
+        {
+            let problem = format!("Recorded on {} but no longer present", record.recorded_at);
+            report.push(json!({ "file": name, "status": "missing", "sha256": record.sha256, "problems": [problem] }));
+        }
+    }
+
+    let failed = report
+        .iter()
+        .filter(|r| !matches!(r["status"].as_str(), Some("ok") | Some("unrecorded")))
+        .count();
+    output::emit(&report, || {
+        for entry in report.iter()
+        {
+            println!("{:<10} {}", entry["status"].as_str().unwrap_or_default(), entry["file"].as_str().unwrap_or_default());
+            for problem in entry["problems"].as_array().into_iter().flatten()
+            {
+                println!("           {}", problem.as_str().unwrap_or_default());
+            }
+        }
+        eprintln!("{} file(s) checked in {}, {} failed.", report.len(), dir.display(), failed);
+    });
+
+    match failed
+    {
+        0 => Ok(()),
+        n => Err(FlotillaError::Validation(format!("{} of {} file(s) in {} failed the check", n, report.len(), dir.display()))),
+    }
+}
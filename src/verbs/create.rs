@@ -0,0 +1,70 @@
+use crate::interface::{CreateWhat, CreateCollectionOptions};
+use flotilla::api::{IdType, NewCollection};
+use flotilla::api::blocking::Flotilla;
+use flotilla::config::Config;
+use flotilla::session::Session;
+use flotilla::FlotillaError;
+use crate::output;
+
+pub fn exec(config: Config, what: CreateWhat) -> Result<(), FlotillaError>
+{
+    match what
+    {
+        CreateWhat::Collection(options) => create_collection(config, options),
+    }
+}
+
+fn create_collection(config: Config, options: CreateCollectionOptions) -> Result<(), FlotillaError>
+{
+    let mut collection = match &options.file
+    {
+        Some(file) => {
+            let contents = std::fs::read_to_string(file)
+                .map_err(|e| FlotillaError::Io(format!("Could not read {}: {}", file.display(), e)))?;
+            serde_json::from_str::<NewCollection>(&contents)
+                .map_err(|e| FlotillaError::parse(e, &contents).context(format!("Could not parse {}", file.display())))?
+        },
+        None => NewCollection::default(),
+    };
+
+    if let Some(name) = options.name
+    {
+        collection.name = name;
+    }
+    if let Some(description) = options.description
+    {
+        collection.description = description;
+    }
+    if let Some(icon) = options.icon
+    {
+        collection.icon = icon;
+    }
+    if let Some(color) = options.color
+    {
+        collection.color = color;
+    }
+    if options.public.unwrap_or(false)
+    {
+        collection.is_public = true;
+    }
+    collection.ship_ids.extend(options.ship);
+
+    if collection.name.trim().is_empty()
+    {
+        return Err(FlotillaError::Usage("A collection needs a name. Use --name or provide collectionName in the JSON file.".to_string()));
+    }
+    let config = config
+        .load_all(None,None,None)?;
+
+    let session = Session::load_for(&config);
+
+    let flt = Flotilla::new(config, session);
+    collection.ship_ids = flt.resolve_ids(&collection.ship_ids, Some(IdType::Ship))?;
+    let created = flt
+        .create_collection(&collection)
+        .map_err(|e| e.context("Could not create collection"))?;
+
+    eprintln!("Created collection '{}'", created.name);
+    output::emit(&created, || println!("{}", created.id));
+    Ok(())
+}
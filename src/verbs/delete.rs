@@ -0,0 +1,116 @@
+use flotilla::api::{IdType, get_id_type};
+use flotilla::api::blocking::Flotilla;
+use flotilla::config::Config;
+use flotilla::session::Session;
+use flotilla::FlotillaError;
+use super::confirm;
+use crate::output;
+use serde_json::json;
+use std::collections::HashSet;
+
+pub fn exec(config: Config, ids: Vec<String>, yes: Option<bool>) -> Result<(), FlotillaError>
+{
+    if ids.is_empty()
+    {
+        return Err(FlotillaError::Usage("No ids given. Please provide one or more ship or collection ids to delete.".to_string()));
+    }
+
+    let config = config
+        .load_all(None,None,None)?;
+
+    let session = Session::load_for(&config);
+
+    let flt = Flotilla::new(config, session);
+    let mut ids = flt.resolve_ids(&ids, None)?;
+    // The same object may be named twice, e.g. once by id and once by name
+    let mut seen = HashSet::new();
+    ids.retain(|id| seen.insert(id.clone()));
+
+    // Look everything up first so the user sees exactly what will be removed
+    let mut results: Vec<(String, Result<(), FlotillaError>)> = Vec::new();
+    let mut targets: Vec<String> = Vec::new();
+    let mut skipped: Vec<String> = Vec::new();
+    for id in ids.iter()
+    {
+        match describe(&flt, id)
+        {
+            Ok(description) => {
+                match output::is_human()
+                {
+                    true => println!("{}", description),
+                    false => eprintln!("{}", description),
+                }
+                targets.push(id.clone());
+            },
+            Err(e) => results.push((id.clone(), Err(e))),
+        }
+    }
+
+    if !targets.is_empty()
+    {
+        let confirmed = yes.unwrap_or(false);
+        if !confirmed && !confirm(&format!("Are you sure you want to delete {} object(s)? This cannot be undone.", targets.len()))?
+        {
+            eprintln!("Aborting.");
+            skipped.append(&mut targets);
+        }
+    }
+
+    for id in targets.iter()
+    {
+        results.push((id.clone(), flt.delete_by_id(id)));
+    }
+
+    let mut failures = Vec::new();
+    let mut report = Vec::new();
+    eprintln!();
+    for id in ids.iter()
+    {
+        if skipped.contains(id)
+        {
+            eprintln!("{} - Skipped", id);
+            report.push(json!({ "id": id, "deleted": false, "error": null }));
+        }
+        else if let Some(pos) = results.iter().position(|(x, _)| x == id)
+        {
+            match results.remove(pos).1
+            {
+                Ok(_) => {
+                    eprintln!("{} - Deleted", id);
+                    report.push(json!({ "id": id, "deleted": true, "error": null }));
+                },
+                Err(e) => {
+                    eprintln!("{} - Not deleted: {}", id, e);
+                    report.push(json!({ "id": id, "deleted": false, "error": e.to_json() }));
+                    failures.push(e.context(id.clone()));
+                },
+            }
+        }
+    }
+
+    output::emit(&report, || {});
+    FlotillaError::from_many(failures)
+}
+
+fn describe(flt: &Flotilla, id: &String) -> Result<String, FlotillaError>
+{
+    match get_id_type(id)?
+    {
+        IdType::Collection => {
+            let collection = flt.get_current_collection(id)?;
+            Ok(format!("Collection {} '{}': {} ship(s), {}",
+                       collection.id,
+                       collection.name,
+                       collection.ship_ids.len(),
+                       if collection.is_public { "public" } else { "private" }))
+        },
+        IdType::Ship => {
+            let ship = flt.get_current_ship(id)?;
+            Ok(format!("Ship {} '{}': {} download(s), referenced by {} collection(s)",
+                       ship.short_id,
+                       ship.name,
+                       ship.downloads,
+                       ship.num_collections))
+        },
+    }
+}
@@ -0,0 +1,30 @@
+use flotilla::api;
+use flotilla::config;
+use flotilla::session;
+use flotilla::FlotillaError;
+use super::get::{download_all, DLEndpoint};
+use crate::output;
+
+pub fn exec(config: config::Config, ids: Vec<String>, public: Option<bool>) -> Result<(), FlotillaError>
+{
+    if ids.is_empty()
+    {
+        return Err(FlotillaError::Usage("No ids given. Please provide one or more ship or collection ids to download.".to_string()));
+    }
+    let config = config.load_all(None,None,None)?;
+    let session = session::Session::load_for(&config);
+    let flt = api::Flotilla::new(config, session);
+    let eptype = match public.unwrap_or(false) {
+        true => DLEndpoint::Public,
+        false => DLEndpoint::Private,
+    };
+    std::fs::create_dir_all(&flt.config.download_path)
+        .map_err(|e| FlotillaError::Io(format!("Could not create {}: {}", flt.config.download_path, e)))?;
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap().block_on(async {
+            let ids = flt.resolve_ids(&ids, None).await?;
+            download_all(&flt, ids, eptype, Some(output::progress()), None).await
+        })
+}
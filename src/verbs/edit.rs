@@ -1,31 +1,35 @@
 use crate::interface::EditOperation;
-use crate::api::{Flotilla, IdType, get_id_type, Ship, Collection};
-use crate::config::Config;
-use crate::session::Session;
-use similar::{TextDiff, ChangeTag};
+use flotilla::api::{IdType, get_id_type, Ship, Collection};
+use flotilla::api::blocking::Flotilla;
+use flotilla::config::Config;
+use flotilla::session::Session;
+use flotilla::FlotillaError;
 use serde_json::json;
+use super::{confirm, print_diff};
+use crate::output;
 
 
-pub fn exec(id: String, operation: EditOperation, yes:Option<bool> ) -> Result<(), String>{
+pub fn exec(config: Config, id: String, operation: EditOperation, yes:Option<bool> ) -> Result<(), FlotillaError>{
 
-    let config = Config::new().load_env().load_file().map_err(|e| format!("Application Error: Could not load configuration file. Please file a bug! {}", e))?;
-    let session = Session::new().load_all();
-    if session.expired()
-    {
-        Err("Session expired. Please login.".to_string())?;
-    }
+    let config = config.load_all(None,None,None)?;
+    let session = Session::load_for(&config);
 
-    let json_data = Flotilla::new(&config, &session).get_json_by_id(&id)?;
+    let flt = Flotilla::new(config, session);
+    let id = flt.resolve_id(&id, None)?;
+    let json_data = flt.get_json_by_id(&id)?;
 
     if json_data.is_null()
     {
-        return Err("Could not find object with that ID".to_string());
+        return Err(FlotillaError::NotFound(id));
     }
     let mut new_json_data = json_data.clone();
     new_json_data = match operation
     {
         EditOperation::Add(x) => {
-            assert!(new_json_data[&x.key].is_array(), "Cannot add to non-array field");
+            if !new_json_data[&x.key].is_array()
+            {
+                return Err(FlotillaError::Usage("Cannot add to non-array field".to_string()));
+            }
             x.values
                 .iter()
                 .for_each(|v| new_json_data[&x.key].as_array_mut().unwrap()
@@ -35,9 +39,8 @@ pub fn exec(id: String, operation: EditOperation, yes:Option<bool> ) -> Result<(
         EditOperation::Remove(x) => {
             if !new_json_data[&x.key].is_array()
             {
-                return Err("Cannot remove from non-array field".to_string());
+                return Err(FlotillaError::Usage("Cannot remove from non-array field".to_string()));
             }
-            assert!(new_json_data[&x.key].is_array(), "Cannot remove from non-array field");
             x.values
                 .iter()
                 .for_each(|v| new_json_data[&x.key].as_array_mut().unwrap()
@@ -46,73 +49,64 @@ pub fn exec(id: String, operation: EditOperation, yes:Option<bool> ) -> Result<(
         },
         EditOperation::Set(x) => {
 
+            let value = || x.values.first()
+                .ok_or_else(|| FlotillaError::Usage(format!("No value given for {}", x.key)));
+            let invalid = |what: &str| FlotillaError::Usage(format!("{} must be {}", x.key, what));
             if new_json_data[&x.key].is_array()
             {
                 new_json_data[&x.key] = json!(x.values);
             } else if new_json_data[&x.key].is_boolean()
             {
-                new_json_data[&x.key] = json!(x.values[0].parse::<bool>().unwrap());
+                new_json_data[&x.key] = json!(value()?.parse::<bool>().map_err(|_| invalid("true or false"))?);
             } else if new_json_data[&x.key].is_number()
             {
-                new_json_data[&x.key] = json!(x.values[0].parse::<f64>().unwrap());
-            } else 
-            {
-                new_json_data[&x.key] = json!(x.values[0]);
+                new_json_data[&x.key] = json!(value()?.parse::<f64>().map_err(|_| invalid("a number"))?);
+            } else {
+                new_json_data[&x.key] = json!(value()?);
             }
             new_json_data
         },
     };
 
+    let report = |applied: bool| json!({ "id": id, "before": json_data, "after": new_json_data, "applied": applied });
+
     if json_data == new_json_data
     {
-        println!("No changes made.");
+        output::emit(&report(false), || println!("No changes made."));
         return Ok(());
     }
 
-    for change in TextDiff::from_lines(
-            &serde_json::to_string_pretty(&json_data).unwrap(), 
-            &serde_json::to_string_pretty(&new_json_data).unwrap()
-        )
-        .iter_all_changes() {
-        let sign = match change.tag() {
-            ChangeTag::Delete => "-",
-            ChangeTag::Insert => "+",
-            ChangeTag::Equal => " ",
-        };
-        print!("{}{}", sign, change);
+    if output::is_human()
+    {
+        print_diff(&json_data, &new_json_data);
     }
 
     let confirmed = yes.unwrap_or(false);
 
-    if !confirmed
+    if !confirmed && !confirm("Are you sure you want to make these changes?")?
     {
-        println!();
-        println!("Are you sure you want to make these changes? (type yes to confirm)");
-        println!();
-        let mut input = String::new();
-        std::io::stdin().read_line(&mut input).map_err(|e| format!("Application Error: Could not read input. Please file a bug! {}", e))?;
-        if input.trim().to_ascii_lowercase() != "yes"
-        {
-            println!("Aborting.");
-            return Ok(());
-        }
+        output::emit(&report(false), || println!("Aborting."));
+        return Ok(());
     }
 
     eprintln!("Sending changes to server...");
 
-    match get_id_type(&id)
+    match get_id_type(&id)?
     {
         IdType::Collection => {
-            let collection: Collection = serde_json::from_value(new_json_data).unwrap();
-            Flotilla::new(&config, &session).set_collection(collection).map_err(|e| format!("Application Error: Could not set collection. Please file a bug! {}", e))?;
+            let collection: Collection = serde_json::from_value(new_json_data.clone())
+                .map_err(|e| FlotillaError::parse(e, &new_json_data.to_string()))?;
+            flt.set_collection(collection).map_err(|e| e.context("Could not set collection"))?;
         },
         IdType::Ship => {
-            let ship: Ship = serde_json::from_value(new_json_data).unwrap();
-            Flotilla::new(&config, &session).set_ship(ship).map_err(|e| format!("Application Error: Could not set ship. Please file a bug! {}", e))?;
+            let ship: Ship = serde_json::from_value(new_json_data.clone())
+                .map_err(|e| FlotillaError::parse(e, &new_json_data.to_string()))?;
+            flt.set_ship(ship).map_err(|e| e.context("Could not set ship"))?;
         },
     }
 
     eprintln!("Changes sent.");
+    output::emit(&report(true), || {});
     Ok(())
 
 }
@@ -1,4 +1,132 @@
-pub fn exec() -> Result<(), String>
+use flotilla::api;
+use flotilla::config::Config;
+use flotilla::session::Session;
+use flotilla::FlotillaError;
+use super::get::{download_each, DLEndpoint, Downloaded};
+use crate::output;
+use std::collections::HashMap;
+use std::path::Path;
+
+const STATE_FILE: &str = ".flotilla-fetch.json";
+
+/// What we last downloaded for each id, so unchanged items can be skipped
+#[derive(serde::Serialize, serde::Deserialize, Default)]
+struct FetchState {
+    items: HashMap<String, FetchedItem>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+struct FetchedItem {
+    fingerprint: String,
+    path: String,
+}
+
+impl FetchState {
+    fn load(folder: &str) -> FetchState {
+        let contents = std::fs::read_to_string(Path::new(folder).join(STATE_FILE)).unwrap_or_default();
+        serde_json::from_str(&contents).unwrap_or_default()
+    }
+
+    fn save(&self, folder: &str) -> Result<(), FlotillaError> {
+        let path = Path::new(folder).join(STATE_FILE);
+        let file = std::fs::File::create(&path)
+            .map_err(|e| FlotillaError::Io(format!("Could not save fetch state in {}: {}", path.display(), e)))?;
+        serde_json::to_writer_pretty(&file, self)
+            .map_err(|e| FlotillaError::Io(format!("Could not save fetch state in {}: {}", path.display(), e)))
+    }
+
+    fn is_current(&self, id: &String, fingerprint: &String) -> bool {
+        match self.items.get(id) {
+            Some(item) => &item.fingerprint == fingerprint && Path::new(&item.path).exists(),
+            None => false,
+        }
+    }
+}
+
+pub fn exec(config: Config) -> Result<(), FlotillaError>
+{
+    let config = config
+        .load_all(None,None,None)?;
+
+    let session = Session::load_for(&config);
+
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(fetch_all(api::Flotilla::new(config, session)))
+}
+
+async fn fetch_all(flt: api::Flotilla) -> Result<(), FlotillaError>
+{
+    let user_data = flt.get_user_data().await?;
+
+    let folder = flt.config.download_path.clone();
+    std::fs::create_dir_all(&folder)
+        .map_err(|e| FlotillaError::Io(format!("Could not create {}: {}", folder, e)))?;
+    let mut state = FetchState::load(&folder);
+
+    let fingerprints = fingerprints(&user_data);
+    let (current, stale): (Vec<String>, Vec<String>) = fingerprints
+        .keys()
+        .cloned()
+        .partition(|id| state.is_current(id, &fingerprints[id]));
+
+    eprintln!("{} ship(s) and {} collection(s) owned, {} already up to date.",
+              user_data.ships.len(),
+              user_data.collections.len(),
+              current.len());
+
+    let results = match stale.is_empty()
+    {
+        true => Vec::new(),
+        false => download_each(&flt, stale, DLEndpoint::Private, Some(output::progress())).await,
+    };
+    output::emit(&serde_json::json!({
+        "ships": user_data.ships.len(),
+        "collections": user_data.collections.len(),
+        "up_to_date": current,
+        "downloads": results.iter().map(Downloaded::from).collect::<Vec<Downloaded>>(),
+    }), || {});
+
+    let mut errors = Vec::new();
+    for (id, result) in results
+    {
+        match result
+        {
+            Ok(path) => {
+                state.items.insert(id.clone(), FetchedItem { fingerprint: fingerprints[&id].clone(), path });
+            },
+            Err(e) => errors.push(e),
+        }
+    }
+    state.save(&folder)?;
+
+    FlotillaError::from_many(errors)
+}
+
+/// A string which changes whenever the downloadable content of an item changes
+fn fingerprints(user_data: &api::UserData) -> HashMap<String, String>
 {
-    Err(format!("Not implemented yet: fetch"))
+    let uploaded: HashMap<&String, u64> = user_data.ships
+        .iter()
+        .map(|s| (&s.id, s.uploaded))
+        .collect();
+
+    let ships = user_data.ships
+        .iter()
+        .map(|s| (s.id.clone(), format!("{}:{}", s.name, s.uploaded)));
+
+    let collections = user_data.collections
+        .iter()
+        .map(|c| {
+            let mut members: Vec<String> = c.ship_ids
+                .iter()
+                .map(|id| format!("{}@{}", id, uploaded.get(id).map(|u| u.to_string()).unwrap_or_default()))
+                .collect();
+            members.sort();
+            (c.id.clone(), format!("{}:{}", c.name, members.join(",")))
+        });
+
+    ships.chain(collections).collect()
 }
@@ -1,11 +1,21 @@
-use crate::api;
-use crate::config;
-use crate::session;
+use flotilla::api;
+use flotilla::config;
+use flotilla::install::{sanitize_file_name, Collision, Installed, Installer};
+use flotilla::integrity::{self, Manifest};
+use flotilla::session;
+use flotilla::FlotillaError;
+use crate::interface::GetOptions;
+use crate::output;
 use futures::{stream,StreamExt};
-use reqwest::Client;
+use flotilla::api::retry;
+use reqwest::header::{ETAG, IF_RANGE, LAST_MODIFIED, RANGE};
+use reqwest::{Method, RequestBuilder, StatusCode};
 use indicatif::{ProgressBar, ProgressStyle};
 use indicatif::MultiProgress;
-use tokio::io::AsyncWriteExt;
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+use std::collections::{BTreeMap, HashSet};
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
 pub enum DLEndpoint{
@@ -13,14 +23,41 @@ pub enum DLEndpoint{
     Private,
     Both,
 }
-pub fn exec(ids: Vec<String>, both: Option<bool>, public: Option<bool>) -> Result<(), String>
+pub fn exec(config: config::Config, options: GetOptions) -> Result<(), FlotillaError>
 {
-    let public = public.unwrap_or(false);
-    let config = config::Config::new().load_all(None,None,None);
-    let session = session::Session::new().load_all();
-    let multi = MultiProgress::new();
-    let flt = api::Flotilla::new(&config, &session);
-    let eptype = match (both, public) {
+    let public = options.public.unwrap_or(false);
+    let collision = match options.on_collision.as_deref() {
+        Some(policy) => policy.parse()?,
+        None => Collision::Rename,
+    };
+    let mut config = config.load_all(None,None,None)?;
+    if let Some(out_dir) = options.out_dir
+    {
+        config.download_path = out_dir.display().to_string();
+    }
+    if let Some(template) = options.name_template
+    {
+        config.name_template = Some(template);
+    }
+    if let Some(template) = &config.name_template
+    {
+        render(template, |_| Ok(String::new()))?;
+    }
+    std::fs::create_dir_all(&config.download_path)
+        .map_err(|e| FlotillaError::Io(format!("Could not create {}: {}", config.download_path, e)))?;
+    let installer = match (options.extract_to, options.extract.unwrap_or(false)) {
+        (Some(dir), _) => Some(Installer { dir, collision }),
+        (None, true) => match &config.ships_dir {
+            Some(dir) => Some(Installer { dir: dir.into(), collision }),
+            None => return Err(FlotillaError::Usage("No ships folder to extract into. Use --extract-to DIR, or set ships_dir in the config".to_string())),
+        },
+        (None, false) => None,
+    };
+    let session = session::Session::load_for(&config);
+    let multi = output::progress();
+    let flt = api::Flotilla::new(config, session);
+    let ids = options.ids;
+    let eptype = match (options.both, public) {
         (Some(true), _) => DLEndpoint::Both,
         (_, true) => DLEndpoint::Public,
         _ => DLEndpoint::Private,
@@ -28,154 +65,392 @@ pub fn exec(ids: Vec<String>, both: Option<bool>, public: Option<bool>) -> Resul
     tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .build()
-        .unwrap().block_on( download_all(&flt, ids, eptype, Some(multi)) )
+        .unwrap().block_on(async {
+            let ids = flt.resolve_ids(&ids, None).await?;
+            download_all(&flt, ids, eptype, Some(multi), installer.as_ref()).await
+        })
 }
 
-#[derive(Debug, Clone)]
+/// Placeholders understood by --name-template
+const PLACEHOLDERS: [&str; 7] = ["name", "id", "id8", "short_id", "owner", "date", "source"];
+const DEFAULT_COLLECTION_TEMPLATE: &str = "{name}-{id8}-{source}";
+const DEFAULT_SHIP_TEMPLATE: &str = "{name}";
+
+/// The file name template for one kind of download
+fn name_template(config: &config::Config, kind: api::IdType) -> String
+{
+    match (&config.name_template, kind) {
+        (Some(template), _) => template.clone(),
+        (None, api::IdType::Collection) => DEFAULT_COLLECTION_TEMPLATE.to_string(),
+        (None, api::IdType::Ship) => DEFAULT_SHIP_TEMPLATE.to_string(),
+    }
+}
+
+/// Fills in the placeholders of a file name template. Values cannot add folders to the name.
+/// `value` refuses, with the reason, placeholders which mean nothing for this download
+fn render(template: &str, value: impl Fn(&str) -> Result<String, String>) -> Result<String, FlotillaError>
+{
+    let invalid = |why: String| FlotillaError::Usage(format!("Invalid name template '{}': {}. Placeholders are {{{}}}", template, why, PLACEHOLDERS.join("}, {")));
+    let mut rendered = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{')
+    {
+        rendered.push_str(&rest[..start]);
+        let end = rest[start..].find('}').ok_or_else(|| invalid("unclosed '{'".to_string()))? + start;
+        let key = &rest[start + 1..end];
+        if !PLACEHOLDERS.contains(&key)
+        {
+            return Err(invalid(format!("unknown placeholder {{{}}}", key)));
+        }
+        rendered.push_str(&value(key).map_err(invalid)?.replace(['/', '\\'], "_"));
+        rest = &rest[end + 1..];
+    }
+    rendered.push_str(rest);
+    Ok(rendered)
+}
+
+#[derive(Debug)]
 struct DownloadTask {
     id: String,
+    kind: api::IdType,
     name: String,
     folder_path: String,
     dl_dest: String,
     meta_url: String,
     dl_url: String,
-    client: Client,
-    token_value: String,
+    flt: api::Flotilla,
     postfix: String,
-    result: Option<Result<String, String>>,
+    result: Option<Result<String, FlotillaError>>,
     bar: Option<ProgressBar>,
 }
 
 impl DownloadTask
 {
-    fn new(id: String, folder_path: String, meta_url: String, dl_url:String, client: Client, token_value: String, postfix: String) -> DownloadTask
+    fn new(id: String, kind: api::IdType, folder_path: String, meta_url: String, dl_url:String, flt: api::Flotilla, postfix: String) -> DownloadTask
     {
         DownloadTask {
             id,
+            kind,
             name: "".to_string(),
             folder_path,
             dl_dest: "".to_string(),
             meta_url,
             dl_url,
-            client,
-            token_value,
+            flt,
             postfix,
             result: None,
             bar: None,
         }
     }
 
-    async fn get_metadata(&mut self) -> Result<DownloadTask, String>
+    fn label(&self) -> String
+    {
+        format!("{} [{}]", self.id, self.postfix)
+    }
+
+    /// Public objects are fetched anonymously, so they work without a session
+    fn is_public(&self) -> bool
+    {
+        self.postfix == "public_"
+    }
+
+    async fn request(&self, url: &str) -> Result<RequestBuilder, FlotillaError>
+    {
+        match self.is_public() {
+            true => Ok(self.flt.client().get(url)),
+            false => self.flt.request(Method::GET, url).await.map_err(|e| e.context(self.label())),
+        }
+    }
+
+    async fn get_metadata(&mut self, template: &str) -> Result<(), FlotillaError>
     {
         let pb = self.bar.as_ref();
         pb.unwrap().inc(1);
-        pb.unwrap().set_message(format!("{} [{}] - Getting metadata", self.id, self.postfix));
-        let resp = self.client
-            .get(&self.meta_url)
-            .header("Authorization", &self.token_value)
-            .send().await;
-        pb.unwrap().set_message(format!("{} [{}] - Parsing metadata", self.id, self.postfix));
-        if resp.is_err() {
-            return Err(format!("{} - [{}] {} ... Sorry!", self.id, self.postfix, resp.unwrap_err().to_string()));
-        }
-        let resst = resp.unwrap().error_for_status();
-        let bytes = match resst {
-            Ok(s) => s.bytes().await,
-            Err(e) => {
-                return Err(format!("{} [{}] - Denied (are you logged in? Does this exist?) {}", self.id, self.postfix, e.without_url()));
-            }
+        pb.unwrap().set_message(format!("{} - Getting metadata", self.label()));
+        let txt = self.flt.get_cached(&self.meta_url, self.is_public()).await
+            .map_err(|e| match e {
+                FlotillaError::AuthExpired | FlotillaError::NotFound(_) => e.context(format!("{} - Denied (are you logged in? Does this exist?)", self.label())),
+                _ => e.context(self.label()),
+            })?;
+        pb.unwrap().set_message(format!("{} - Parsing metadata", self.label()));
+        let v = serde_json::from_str::<serde_json::Value>(&txt)
+            .map_err(|e| FlotillaError::parse(e, &txt).context(self.label()))?;
+        let name_key = match self.kind {
+            api::IdType::Collection => "collectionName",
+            api::IdType::Ship => "shipName",
         };
-        let v= serde_json::from_slice::<serde_json::Value>(&bytes.unwrap()).unwrap();
-        if v["collectionName"].is_null() || v["id"].is_null() {
-            return Err(format!("{} [{}] - Bad response from server! ", self.id, self.postfix));
+        if !v[name_key].is_string() || v["id"].is_null() {
+            return Err(FlotillaError::parse(format!("missing {} or id", name_key), &txt).context(format!("{} - Bad response from server!", self.label())));
         }
-        self.name = v["collectionName"].as_str().unwrap().to_string();
-        self.dl_dest = format!("{}/{}-{}-{}.zip", self.folder_path, self.name, self.id[0..8].to_string(), self.postfix);
-        Ok(self.clone())
+        self.name = v[name_key].as_str().unwrap().to_string();
+        let (short_id, owner, extension) = match self.kind {
+            api::IdType::Collection => {
+                (self.id[0..8].to_string(), Some(v["collectionOwner"].as_str().unwrap_or_default().to_string()), "zip")
+            },
+            api::IdType::Ship => {
+                let ship = serde_json::from_value::<api::Ship>(v)
+                    .map_err(|e| FlotillaError::parse(e, &txt).context(format!("{} - Bad response from server!", self.label())))?;
+                self.dl_url = ship.download_url;
+                (ship.short_id, None, "seria")
+            },
+        };
+        let rendered = render(template, |key| match key {
+            "name" => Ok(self.name.clone()),
+            "id" => Ok(self.id.clone()),
+            "id8" => Ok(self.id[0..8].to_string()),
+            "short_id" => Ok(short_id.clone()),
+            "owner" => owner.clone().ok_or_else(|| "{owner} is only known for collections".to_string()),
+            // The day of the download; the server does not say when a collection last changed
+            "date" => Ok(chrono::Utc::now().format("%Y-%m-%d").to_string()),
+            _ => Ok(self.postfix.clone()),
+        }).map_err(|e| e.context(self.label()))?;
+        let mut file_name = sanitize_file_name(&rendered);
+        if !file_name.to_ascii_lowercase().ends_with(&format!(".{}", extension))
+        {
+            file_name = format!("{}.{}", file_name, extension);
+        }
+        self.dl_dest = Path::new(&self.folder_path).join(file_name).display().to_string();
+        Ok(())
     }
 
-    async fn dl(&mut self) -> Result<DownloadTask, String>
+    async fn dl(&mut self) -> Result<(), FlotillaError>
     {
-        let pb = self.bar.as_ref();
-        pb.unwrap().inc(1);
-        pb.unwrap().set_message(format!("{} [{}] - Starting download ... ", self.id, self.postfix));
-        let resp = self.client
-            .get(&self.dl_url)
-            .header("Authorization", &self.token_value)
-            .send().await;
-        if resp.is_err() {
-            return Err(format!("{} [{}] - Library error {} ", self.id, self.postfix, resp.unwrap_err().to_string()));
-        }
-        let resst = resp.unwrap().error_for_status();
-        let resp = match resst {
-            Ok(x) => x,
+        let pb = self.bar.clone().unwrap();
+        pb.inc(1);
+        pb.set_message(format!("{} - Starting download ... ", self.label()));
+        // The final name only appears once the file is complete, so a failed download never looks finished
+        let part = format!("{}.part", self.dl_dest);
+        match self.dl_to(&part, &pb).await
+        {
+            Ok(_) => tokio::fs::rename(&part, &self.dl_dest).await
+                .map_err(|e| FlotillaError::Io(format!("{} - Could not rename {} to {}: {}", self.label(), part, self.dl_dest, e))),
             Err(e) => {
-                match e.status().unwrap().as_u16() {
-                    401 => {
-                        return Err(format!("{} [{}] - {} ", self.id, self.postfix, e.without_url()));
-                    },
-                    404 => {
-                        return Err(format!("{} [{}] - Not Found ", self.id, self.postfix ));
-                    },
-                    500 => {
-                        return Err(format!("{} [{}] - Server error - Possibly a bug! {}", self.id, self.postfix,  e.without_url()));
-                    },
-                    _ => {
-                        return Err(format!("{} [{}] - Unknown Error: {} ", self.id, self.postfix, e.without_url()));
-                    }
+                let _ = tokio::fs::remove_file(&part).await;
+                Err(e)
+            },
+        }
+    }
+
+    /// Downloads into `part`, resuming with Range requests where the connection dropped
+    async fn dl_to(&self, part: &str, pb: &ProgressBar) -> Result<(), FlotillaError>
+    {
+        let write_error = |e: std::io::Error| FlotillaError::Io(format!("{} [{}] - Error writing to file: {}", part, self.postfix, e));
+        let mut file = tokio::fs::File::create(part).await
+            .map_err(|e| FlotillaError::Io(format!("{} - Could not create {}: {}", self.label(), part, e)))?;
+        let policy = retry::Policy::from_config(&self.flt.config);
+        let mut attempt = 1;
+        let mut written: u64 = 0;
+        // What the first response was, so a resumed one is only accepted for the same file
+        let mut validator: Option<String> = None;
+        pb.set_style(ProgressStyle::default_bar()
+                     .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta}) {msg}")
+                     .unwrap()
+                     .progress_chars("#>-"));
+        loop
+        {
+            // Ship files are served from their own download url, which must not see our token
+            let mut req = match self.kind {
+                api::IdType::Collection => self.request(&self.dl_url).await?,
+                api::IdType::Ship => self.flt.client().get(&self.dl_url),
+            };
+            if written > 0
+            {
+                req = req.header(RANGE, format!("bytes={}-", written));
+                if let Some(validator) = &validator
+                {
+                    req = req.header(IF_RANGE, validator);
                 }
             }
-        };
-        let mut file = tokio::fs::File::create(&self.dl_dest).await.map_err(|e| e.to_string()).map_err(|e| e.to_string()).unwrap();
-        let sz = resp.content_length().unwrap_or(0);
-        let mut stream = resp.bytes_stream();
-        pb.unwrap().set_length(sz);
-        pb.unwrap().set_style(ProgressStyle::default_bar()
-                              .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})")
-                              .unwrap()
-                              .progress_chars("#>-"));
-        pb.unwrap().set_message(format!("{} [{}] - Downloading", self.id, self.postfix));
-        while let Some(item) = stream.next().await {
-            if let Ok(bytes) = item{
-                pb.unwrap().inc(bytes.len() as u64);
-                match file.write_all(&bytes).await.map_err(|e| e.to_string()) {
-                    Ok(_) => {},
+            let resp = match self.flt.send_retrying(req).await
+            {
+                // Our offset is past what the server has now, so fetch it all again
+                Err(FlotillaError::Http { status: 416, .. }) if written > 0 => None,
+                Err(e) => return Err(match e {
+                    FlotillaError::Http { status: 500, .. } => e.context(format!("{} - Server error - Possibly a bug!", self.label())),
+                    _ => e.context(self.label()),
+                }),
+                Ok(resp) => Some(resp),
+            };
+            if resp.as_ref().is_none_or(|r| r.status() != StatusCode::PARTIAL_CONTENT) && written > 0
+            {
+                file.set_len(0).await.map_err(write_error)?;
+                file.seek(SeekFrom::Start(0)).await.map_err(write_error)?;
+                written = 0;
+                pb.set_position(0);
+            }
+            let resp = match resp
+            {
+                Some(resp) => resp,
+                None => continue,
+            };
+            if validator.is_none()
+            {
+                validator = resp.headers().get(ETAG)
+                    .filter(|v| !v.as_bytes().starts_with(b"W/"))
+                    .or(resp.headers().get(LAST_MODIFIED))
+                    .and_then(|v| v.to_str().ok())
+                    .map(str::to_string);
+            }
+            let total = resp.content_length().map(|remaining| written + remaining);
+            pb.set_length(total.unwrap_or(0));
+            if attempt == 1
+            {
+                pb.set_message(format!("{} - Downloading", self.label()));
+            }
+
+            let mut failure = None;
+            let mut stream = resp.bytes_stream();
+            while let Some(item) = stream.next().await {
+                match item {
+                    Ok(bytes) => {
+                        file.write_all(&bytes).await.map_err(write_error)?;
+                        written += bytes.len() as u64;
+                        pb.inc(bytes.len() as u64);
+                    },
                     Err(e) => {
-                        return Err(format!("{} [{}] - Error writing to file: {}", self.dl_dest, self.postfix, e));
-                    }
+                        failure = Some(FlotillaError::from(e));
+                        break;
+                    },
                 }
-            } else {
-                return Err(format!("{} [{}] - Stream error: {}", self.id, self.postfix, item.unwrap_err().to_string()));
             }
+            if failure.is_none() && total.is_some_and(|total| written < total)
+            {
+                failure = Some(FlotillaError::Network(format!("connection closed after {} of {} bytes", written, total.unwrap())));
+            }
+            match (failure, policy.delay(attempt, None))
+            {
+                (None, _) => break,
+                (Some(e), None) => return Err(e.context(format!("{} - Stream error", self.label()))),
+                (Some(_), Some(delay)) => {
+                    attempt += 1;
+                    pb.set_message(format!("{} - Resuming after {} bytes (attempt {}/{})", self.label(), written, attempt, policy.attempts));
+                    tokio::time::sleep(delay).await;
+                },
+            }
+        }
+        file.sync_all().await.map_err(write_error)
+    }
+}
+
+/// Checks every completed download and records the good ones in their folder's manifest. A file
+/// which fails the check is kept for inspection, but its task fails
+fn verify(tasks: &mut [DownloadTask])
+{
+    let mut manifests: BTreeMap<PathBuf, Manifest> = BTreeMap::new();
+    for task in tasks.iter_mut().filter(|t| matches!(t.result, Some(Ok(_))))
+    {
+        let path = PathBuf::from(&task.dl_dest);
+        let dir = path.parent().unwrap_or(Path::new(".")).to_path_buf();
+        let outcome = integrity::check_file(&path).and_then(|check| {
+            if !check.is_valid()
+            {
+                return Err(FlotillaError::Validation(format!("{} failed verification:\n  {}", task.dl_dest, check.problems.join("\n  "))));
+            }
+            if !manifests.contains_key(&dir)
+            {
+                manifests.insert(dir.clone(), Manifest::load(&dir)?);
+            }
+            manifests.get_mut(&dir).unwrap().record(&path, &task.id, &check);
+            Ok(())
+        });
+        if let Err(e) = outcome
+        {
+            task.result = Some(Err(e.context(task.label())));
+        }
+    }
+    for (dir, manifest) in manifests.iter()
+    {
+        if let Err(e) = manifest.save(dir)
+        {
+            eprintln!("Warning: {}", e);
         }
-        file.sync_all().await.map_err(|e| e.to_string()).map_err(|e| e.to_string())?;
-        Ok( self.clone() )
     }
 }
 
-pub async fn download_all<'a>(flt: &api::Flotilla<'a>, ids: Vec<String>, eptype: DLEndpoint, multi: Option<MultiProgress>) -> Result<(), String>
+/// Gives every download a file of its own. Two ships of the same name, or a template without
+/// {id}, render to the same name, and two tasks writing one file would corrupt it; later ones
+/// are numbered like `Fleet (2).zip` instead
+fn disambiguate<'a>(dests: impl Iterator<Item = &'a mut String>)
 {
+    // Compared without case, as Windows and macOS do
+    let key = |path: &Path| path.display().to_string().to_lowercase();
+    let mut taken: HashSet<String> = HashSet::new();
+    for dest in dests
+    {
+        let path = PathBuf::from(&*dest);
+        if taken.insert(key(&path))
+        {
+            continue;
+        }
+        let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+        let extension = path.extension().and_then(|x| x.to_str()).unwrap_or_default();
+        let unique = (2..)
+            .map(|n| path.with_file_name(format!("{} ({}).{}", stem, n, extension)))
+            .find(|p| !taken.contains(&key(p)))
+            .unwrap();
+        taken.insert(key(&unique));
+        *dest = unique.display().to_string();
+    }
+}
 
-    let token_value = format!("Bearer {}",flt.session.id_token.replace("\"", ""));
-    let client = Client::new();
+/// What happened to one id, as reported by --output
+#[derive(serde::Serialize)]
+pub struct Downloaded {
+    id: String,
+    path: Option<String>,
+    error: Option<serde_json::Value>,
+    /// The ships installed from it, with --extract
+    #[serde(skip_serializing_if = "Option::is_none")]
+    installed: Option<Vec<Installed>>,
+}
+
+impl From<&(String, Result<String, FlotillaError>)> for Downloaded {
+    fn from((id, result): &(String, Result<String, FlotillaError>)) -> Self {
+        Downloaded {
+            id: id.clone(),
+            path: result.as_ref().ok().cloned(),
+            error: result.as_ref().err().map(|e| e.to_json()),
+            installed: None,
+        }
+    }
+}
+
+/// Downloads every id, returning each id with either the path it was written to or an error
+pub async fn download_each(flt: &api::Flotilla, mut ids: Vec<String>, eptype: DLEndpoint, multi: Option<MultiProgress>) -> Vec<(String, Result<String, FlotillaError>)>
+{
+    let mut invalid = Vec::new();
+    let mut seen = HashSet::new();
+    ids.retain(|id| seen.insert(id.clone()));
 
     let mut tasks = ids.iter().flat_map(|x| {
+        let kind = match api::get_id_type(x) {
+            Ok(kind) => kind,
+            Err(e) => {
+                invalid.push((x.to_string(), Err(e)));
+                return vec![];
+            }
+        };
+        let (what, dl_url) = match kind {
+            api::IdType::Collection => ("collection", flt.url(&format!("/shipyard/collection/download/{}", x))),
+            // The ship download url is only known once we have its metadata
+            api::IdType::Ship => ("ship", String::new()),
+        };
         let pubtask = DownloadTask::new(
             x.to_string(),
+            kind,
             flt.config.download_path.clone(),
-            format!("{}/shipyard/collection/public/{}", flt.config.endpoint, x),
-            format!("{}/shipyard/collection/download/{}", flt.config.endpoint, x),
-            client.clone(),
-            token_value.clone(),
+            flt.url(&format!("/shipyard/{}/public/{}", what, x)),
+            dl_url.clone(),
+            flt.clone(),
             "public_".to_string(),
             );
         let privtask = DownloadTask::new(
             x.to_string(),
+            kind,
             flt.config.download_path.clone(),
-            format!("{}/shipyard/collection/{}", flt.config.endpoint, x),
-            format!("{}/shipyard/collection/download/{}", flt.config.endpoint, x),
-            client.clone(),
-            token_value.clone(),
+            flt.url(&format!("/shipyard/{}/{}", what, x)),
+            dl_url,
+            flt.clone(),
             "private".to_string(),
             );
         match eptype{
@@ -197,55 +472,135 @@ pub async fn download_all<'a>(flt: &api::Flotilla<'a>, ids: Vec<String>, eptype:
             );
         pb.enable_steady_tick( Duration::from_millis(100) );
         pb.set_message(format!("{} [{}] - Starting ... ", task.id, task.postfix));
+        let (retrying, label) = (pb.clone(), task.label());
+        task.flt = task.flt.clone().on_retry(move |attempt, attempts| {
+            retrying.set_message(format!("{} - Retrying (attempt {}/{})", label, attempt, attempts));
+        });
         task.bar = Some(pb);
     }
 
+    // Every name is known before anything is written, so that no two downloads share a file
     let x = stream::iter(&mut tasks)
+        .for_each_concurrent(2, |task|
+                             {
+                                 let template = name_template(&task.flt.config, task.kind);
+                                 async move {
+                                     if let Err(e) = task.get_metadata(&template).await
+                                     {
+                                         task.bar.as_ref().unwrap().abandon_with_message(e.to_string());
+                                         task.result = Some(Err(e));
+                                     }
+                                 }});
+    x.await;
+    disambiguate(tasks.iter_mut().filter(|t| t.result.is_none()).map(|t| &mut t.dl_dest));
+
+    let x = stream::iter(tasks.iter_mut().filter(|t| t.result.is_none()))
         .for_each_concurrent(2, |task|
                              {
                                  let pb = task.bar.clone().unwrap();
                                  async move {
-                                     match task.get_metadata().await
+                                     match task.dl().await
                                      {
                                          Ok(_) => {
-                                             match task.dl().await
-                                             {
-                                                 Ok(_) => {
-                                                     let ok_msg = format!("{} Downloaded to {}", task.id, task.dl_dest);
-                                                     task.result = Some(Ok(ok_msg.clone()));
-                                                     pb.finish_with_message(ok_msg);
-                                                 },
-                                                 Err(e) => {
-                                                     task.result = Some(Err(e.clone()));
-                                                     pb.abandon_with_message(e.to_string());
-                                                 }
-                                             }
+                                             let ok_msg = format!("{} Downloaded to {}", task.id, task.dl_dest);
+                                             task.result = Some(Ok(task.dl_dest.clone()));
+                                             pb.finish_with_message(ok_msg);
                                          },
                                          Err(e) => {
-                                             task.result = Some(Err(e.clone()));
                                              pb.abandon_with_message(e.to_string());
+                                             task.result = Some(Err(e));
                                          }
                                      }
                                  }});
     x.await;
+    verify(&mut tasks);
 
-    //TODO there's probably a better way to do this
-    let errstrings: Vec<String> = tasks
-        .iter()
-        .filter_map(|x| x.result.as_ref().and_then(|r| r.as_ref().err()))
-        .cloned()
-        .collect();
-
-    let _num_ok = tasks
-        .iter()
-        .filter_map(|x| x.result.as_ref().and_then(|r| r.as_ref().ok()))
-        .count();
+    invalid
+        .into_iter()
+        .chain(tasks
+               .into_iter()
+               .filter_map(|x| x.result.map(|r| (x.id, r))))
+        .collect()
+}
 
-    match errstrings.len()
+/// Downloads every id and, given an installer, installs the ships from each download
+pub async fn download_all(flt: &api::Flotilla, ids: Vec<String>, eptype: DLEndpoint, multi: Option<MultiProgress>, installer: Option<&Installer>) -> Result<(), FlotillaError>
+{
+    let results = download_each(flt, ids, eptype, multi).await;
+    let mut report: Vec<Downloaded> = results.iter().map(Downloaded::from).collect();
+    let mut errors: Vec<FlotillaError> = Vec::new();
+    for ((id, result), downloaded) in results.into_iter().zip(report.iter_mut())
     {
-        0 => Ok(()),
-        _ => Err(errstrings.join("\n"))
+        match (result, installer)
+        {
+            (Err(e), _) => errors.push(e),
+            (Ok(path), Some(installer)) => match installer.install(Path::new(&path))
+            {
+                Ok(installed) => downloaded.installed = Some(installed),
+                Err(e) => {
+                    downloaded.error = Some(e.to_json());
+                    errors.push(e.context(format!("{} - Could not install {}", id, path)));
+                },
+            },
+            (Ok(_), None) => {},
+        }
     }
 
+    output::emit(&report, || {
+        let installed: Vec<&Installed> = report.iter().flat_map(|d| d.installed.iter().flatten()).collect();
+        for ship in installed.iter()
+        {
+            match &ship.path
+            {
+                Some(path) => println!("{:<11} {} -> {}", ship.action, ship.ship, path),
+                None => println!("{:<11} {}", ship.action, ship.ship),
+            }
+        }
+        if let Some(installer) = installer
+        {
+            let count = installed.iter().filter(|s| matches!(s.action, "installed" | "renamed" | "overwritten")).count();
+            eprintln!("Installed {} ship(s) into {}", count, installer.dir.display());
+        }
+    });
+
+    FlotillaError::from_many(errors)
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(key: &str) -> Result<String, String> {
+        match key {
+            "name" => Ok("A/B".to_string()),
+            "owner" => Err("{owner} is only known for collections".to_string()),
+            _ => Ok(key.to_uppercase()),
+        }
+    }
+
+    #[test]
+    fn render_fills_placeholders_without_adding_folders() {
+        assert_eq!(render("{name}-{id8}-{source}", values).unwrap(), "A_B-ID8-SOURCE");
+        assert_eq!(render("plain", values).unwrap(), "plain");
+    }
+
+    #[test]
+    fn render_rejects_bad_templates() {
+        assert!(matches!(render("{name", values), Err(FlotillaError::Usage(_))));
+        assert!(matches!(render("{nope}", values), Err(FlotillaError::Usage(_))));
+        match render("{name}-{owner}", values) {
+            Err(FlotillaError::Usage(message)) => assert!(message.contains("only known for collections")),
+            other => panic!("expected a usage error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn disambiguate_numbers_repeated_names() {
+        let mut dests: Vec<String> = ["d/Zeta.seria", "d/Fleet.zip", "d/zeta.seria", "d/Zeta.seria"]
+            .iter()
+            .map(|d| d.to_string())
+            .collect();
+        disambiguate(dests.iter_mut());
+        assert_eq!(dests, ["d/Zeta.seria", "d/Fleet.zip", "d/zeta (2).seria", "d/Zeta (3).seria"]);
+    }
+}
@@ -1,44 +1,253 @@
-use crate::config::Config;
-use crate::session::Session;
-use crate::api;
+use crate::interface::ListOptions;
+use flotilla::config::Config;
+use flotilla::session::Session;
+use flotilla::api::{self, resolve_id, Collection, IdType, Ship, UserData};
+use flotilla::FlotillaError;
+use crate::output::{self, Format};
+use chrono::{DateTime, NaiveDate, Utc};
+use regex::Regex;
+use std::cmp::Ordering;
 
-pub fn exec(what: Option<String>) -> Result<(), String> {
+/// A column of the table view: how to show it and how to sort by it
+struct Column<T: 'static> {
+    name: &'static str,
+    shown_by_default: bool,
+    cell: fn(&T) -> String,
+    order: fn(&T, &T) -> Ordering,
+}
+
+const SHIP_COLUMNS: &[Column<Ship>] = &[
+    Column { name: "id", shown_by_default: true, cell: |s| s.short_id.clone(), order: |a, b| a.short_id.cmp(&b.short_id) },
+    Column { name: "name", shown_by_default: true, cell: |s| s.name.clone(), order: |a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()) },
+    Column { name: "downloads", shown_by_default: true, cell: |s| s.downloads.to_string(), order: |a, b| a.downloads.cmp(&b.downloads) },
+    Column { name: "uploaded", shown_by_default: true, cell: |s| s.uploaded_at().map(|t| t.format("%Y-%m-%d").to_string()).unwrap_or_default(), order: |a, b| a.uploaded_at().cmp(&b.uploaded_at()) },
+    Column { name: "collections", shown_by_default: true, cell: |s| s.num_collections.to_string(), order: |a, b| a.num_collections.cmp(&b.num_collections) },
+    Column { name: "full_id", shown_by_default: false, cell: |s| s.id.clone(), order: |a, b| a.id.cmp(&b.id) },
+    Column { name: "file", shown_by_default: false, cell: |s| s.file_name.clone(), order: |a, b| a.file_name.cmp(&b.file_name) },
+    Column { name: "url", shown_by_default: false, cell: |s| s.download_url.clone(), order: |a, b| a.download_url.cmp(&b.download_url) },
+];
 
-    let config = Config::new()
-        .load_env()
-        .load_file()
-        .map_err(|e| 
-                 format!("Application Error: Could not load configuration file. Please file a bug! {}", e))?;
+const COLLECTION_COLUMNS: &[Column<Collection>] = &[
+    Column { name: "name", shown_by_default: true, cell: |c| c.name.clone(), order: |a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()) },
+    Column { name: "public", shown_by_default: true, cell: |c| if c.is_public { "yes" } else { "no" }.to_string(), order: |a, b| a.is_public.cmp(&b.is_public) },
+    Column { name: "ships", shown_by_default: true, cell: |c| c.ship_ids.len().to_string(), order: |a, b| a.ship_ids.len().cmp(&b.ship_ids.len()) },
+    Column { name: "owner", shown_by_default: true, cell: |c| c.owner.clone(), order: |a, b| a.owner.cmp(&b.owner) },
+    Column { name: "id", shown_by_default: false, cell: |c| c.id.clone(), order: |a, b| a.id.cmp(&b.id) },
+    Column { name: "description", shown_by_default: false, cell: |c| c.description.clone(), order: |a, b| a.description.cmp(&b.description) },
+    Column { name: "url", shown_by_default: false, cell: |c| c.public_url.clone(), order: |a, b| a.public_url.cmp(&b.public_url) },
+];
+
+/// The filtering options, checked and ready to apply
+struct Filters {
+    name: Option<Regex>,
+    public: Option<bool>,
+    min_downloads: Option<u32>,
+    uploaded_since: Option<DateTime<Utc>>,
+    in_collection: Option<String>,
+}
+
+impl Filters {
+    fn from_options(options: &ListOptions) -> Result<Filters, FlotillaError>
+    {
+        let public = match (options.public.unwrap_or(false), options.private.unwrap_or(false))
+        {
+            (true, true) => return Err(FlotillaError::Usage("Please give only one of --public and --private".to_string())),
+            (true, false) => Some(true),
+            (false, true) => Some(false),
+            (false, false) => None,
+        };
+        Ok(Filters {
+            name: options.name.as_deref().map(name_pattern).transpose()?,
+            public,
+            min_downloads: options.min_downloads,
+            uploaded_since: options.uploaded_since.as_deref().map(parse_date).transpose()?,
+            in_collection: options.in_collection.clone(),
+        })
+    }
 
-    let session = Session::new().load_all();
-    if session.expired()
+    fn for_ships_only(&self) -> bool
     {
-        return Err("Session expired. Please login.".to_string());
+        self.min_downloads.is_some() || self.uploaded_since.is_some() || self.in_collection.is_some()
     }
 
-    let flotilla = api::Flotilla::new(&config, &session);
-    let user_data = flotilla.get_user_data().map_err(|e| format!("Error: {}", e))?;
+    fn for_collections_only(&self) -> bool
+    {
+        self.public.is_some()
+    }
+
+    fn apply(&self, user_data: &mut UserData) -> Result<(), FlotillaError>
+    {
+        let members: Option<Vec<String>> = match &self.in_collection
+        {
+            Some(query) => {
+                let (id, _) = resolve_id(user_data, query, Some(IdType::Collection))?;
+                user_data.collections.iter().find(|c| c.id == id).map(|c| c.ship_ids.clone())
+            },
+            None => None,
+        };
+
+        user_data.ships.retain(|ship| {
+            self.name.as_ref().is_none_or(|re| re.is_match(&ship.name))
+                && self.min_downloads.is_none_or(|min| ship.downloads >= min)
+                && self.uploaded_since.is_none_or(|since| ship.uploaded_at().is_some_and(|t| t >= since))
+                && members.as_ref().is_none_or(|ids| ids.contains(&ship.id))
+        });
+        user_data.collections.retain(|collection| {
+            self.name.as_ref().is_none_or(|re| re.is_match(&collection.name))
+                && self.public.is_none_or(|public| collection.is_public == public)
+        });
+        Ok(())
+    }
+}
 
-    return match what.as_deref()
+/// A case-insensitive glob, or a regex when written as /regex/
+fn name_pattern(pattern: &str) -> Result<Regex, FlotillaError>
+{
+    let regex = match pattern.len() > 1 && pattern.starts_with('/') && pattern.ends_with('/')
     {
-        None => {
-            let user_data_json = serde_json::to_string_pretty(&user_data).expect("Application Error: Could not serialize user data. Please file a bug!");
-            println!("{}", &user_data_json);
-            Ok(())
+        true => pattern[1..pattern.len() - 1].to_string(),
+        false => {
+            let glob: String = pattern.chars().map(|c| match c {
+                '*' => ".*".to_string(),
+                '?' => ".".to_string(),
+                _ => regex::escape(&c.to_string()),
+            }).collect();
+            format!("^{}$", glob)
         },
-        Some("ships") => {
-            let user_data_json = serde_json::to_string_pretty(&user_data.ships).expect("Application Error: Could not serialize user data. Please file a bug!");
-            println!("{}", &user_data_json);
-            Ok(())
+    };
+    Regex::new(&format!("(?i){}", regex))
+        .map_err(|e| FlotillaError::Usage(format!("Invalid --name pattern '{}': {}", pattern, e)))
+}
+
+fn parse_date(date: &str) -> Result<DateTime<Utc>, FlotillaError>
+{
+    if let Ok(day) = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+    {
+        return Ok(day.and_hms_opt(0, 0, 0).unwrap().and_utc());
+    }
+    DateTime::parse_from_rfc3339(date)
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|_| FlotillaError::Usage(format!("Invalid date '{}'. Please use YYYY-MM-DD", date)))
+}
+
+pub fn exec(config: Config, options: ListOptions) -> Result<(), FlotillaError> {
+
+    let filters = Filters::from_options(&options)?;
+
+    // Filters which only concern one kind of item also select that kind
+    let (ships, collections) = match options.what.as_deref()
+    {
+        None | Some("both") => (
+            filters.for_ships_only() || !filters.for_collections_only(),
+            filters.for_collections_only() || !filters.for_ships_only(),
+        ),
+        Some("ships") if filters.for_collections_only() => {
+            return Err(FlotillaError::Usage("--public and --private only apply to collections".to_string()))
         },
-        Some("collections") => {
-            let user_data_json = serde_json::to_string_pretty(&user_data.collections).expect("Application Error: Could not serialize user data. Please file a bug!");
-            println!("{}", &user_data_json);
-            Ok(())
+        Some("collections") if filters.for_ships_only() => {
+            return Err(FlotillaError::Usage("--min-downloads, --uploaded-since and --in-collection only apply to ships".to_string()))
         },
+        Some("ships") => (true, false),
+        Some("collections") => (false, true),
         _ => {
-            Err("Invalid argument. Please use 'ships' or 'collections'".to_string())
+            return Err(FlotillaError::Usage("Invalid argument. Please use 'ships' or 'collections'".to_string()))
+        }
+    };
+
+    let requested: Option<Vec<String>> = options.columns
+        .as_ref()
+        .map(|c| c.split(',').map(|x| x.trim().to_string()).filter(|x| !x.is_empty()).collect());
+    let mut known: Vec<&str> = Vec::new();
+    if ships
+    {
+        known.extend(SHIP_COLUMNS.iter().map(|c| c.name));
+    }
+    if collections
+    {
+        known.extend(COLLECTION_COLUMNS.iter().map(|c| c.name));
+    }
+    for name in requested.iter().flatten().chain(options.sort.iter())
+    {
+        if !known.contains(&name.as_str())
+        {
+            return Err(FlotillaError::Usage(format!("Unknown column '{}'. Please use one of: {}", name, known.join(", "))));
+        }
+    }
+
+    let config = config
+        .load_all(None,None,None)?;
+
+    let session = Session::load_for(&config);
+
+    let flotilla = api::blocking::Flotilla::new(config, session);
+    let mut user_data = flotilla.get_user_data()?;
+    filters.apply(&mut user_data)?;
+
+    let reverse = options.reverse.unwrap_or(false);
+    sort(&mut user_data.ships, SHIP_COLUMNS, options.sort.as_deref(), reverse);
+    sort(&mut user_data.collections, COLLECTION_COLUMNS, options.sort.as_deref(), reverse);
+
+    if matches!(output::format(), Format::Human | Format::Table)
+    {
+        let mut tables = Vec::new();
+        if ships
+        {
+            tables.extend(table(&user_data.ships, SHIP_COLUMNS, &requested).map(|t| ("Ships", t)));
+        }
+        if collections
+        {
+            tables.extend(table(&user_data.collections, COLLECTION_COLUMNS, &requested).map(|t| ("Collections", t)));
         }
+        let titled = tables.len() > 1;
+        let printed: Vec<String> = tables
+            .into_iter()
+            .map(|(title, table)| match titled {
+                true => format!("{}\n{}", title, table),
+                false => table,
+            })
+            .collect();
+        print!("{}", printed.join("\n"));
+        return Ok(());
     }
 
+    match (ships, collections)
+    {
+        (true, false) => output::emit(&user_data.ships, || {}),
+        (false, true) => output::emit(&user_data.collections, || {}),
+        _ => output::emit(&user_data, || {}),
+    }
+    Ok(())
+}
+
+/// Sorts by the named column, if this kind of item has it
+fn sort<T>(items: &mut [T], columns: &[Column<T>], by: Option<&str>, reverse: bool)
+{
+    if let Some(column) = by.and_then(|name| columns.iter().find(|c| c.name == name))
+    {
+        items.sort_by(column.order);
+    }
+    if reverse
+    {
+        items.reverse();
+    }
+}
+
+/// The requested columns which this kind of item has, or its default ones. None if it has none of them
+fn table<T>(items: &[T], columns: &[Column<T>], requested: &Option<Vec<String>>) -> Option<String>
+{
+    let shown: Vec<&Column<T>> = match requested
+    {
+        Some(names) => names.iter().filter_map(|name| columns.iter().find(|c| c.name == name)).collect(),
+        None => columns.iter().filter(|c| c.shown_by_default).collect(),
+    };
+    if shown.is_empty()
+    {
+        return None;
+    }
+    let header: Vec<String> = shown.iter().map(|c| c.name.to_string()).collect();
+    let rows: Vec<Vec<String>> = items
+        .iter()
+        .map(|item| shown.iter().map(|c| (c.cell)(item)).collect())
+        .collect();
+    Some(output::format_table(&header, &rows))
 }
@@ -1,18 +1,18 @@
-use crate::config::Config;
-use crate::api;
-pub fn exec(username: Option<String>, password: Option<String>, endpoint: Option<String>) -> Result<(), String> 
+use flotilla::config::Config;
+use flotilla::api;
+use flotilla::FlotillaError;
+use crate::output;
+use super::session_json;
+pub fn exec(config: Config, username: Option<String>, password: Option<String>, endpoint: Option<String>) -> Result<(), FlotillaError> 
 {
 
-    let config = Config::new().load_all(username, password, endpoint);
-    match api::login(&config) {
-        Ok(sess) => {
-            println!("Logged in until {}", 
-                     chrono::NaiveDateTime::from_timestamp_opt(sess.expiration_unix, 0).unwrap().to_string());
-            Ok(())
-        },
-        Err(e) => {
-            Err(format!("Could not log in: {}", e))
-        }
-    }
+    let config = config.load_all(username, password, endpoint)?;
+    let sess = api::blocking::login(&config).map_err(|e| match e {
+        FlotillaError::AuthExpired => e.context("No password given or stored. Run setup, or pass -p"),
+        _ => e.context("Could not log in"),
+    })?;
+    output::emit(&session_json(&sess), || println!("Logged in until {}", 
+             chrono::DateTime::from_timestamp(sess.expiration_unix, 0)
+                 .map(|t| t.to_string()).unwrap_or_default()));
+    Ok(())
 }
-
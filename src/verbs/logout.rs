@@ -1,16 +1,55 @@
-use crate::config;
-use crate::session::Session;
+use flotilla::config;
+use flotilla::credentials;
+use flotilla::session::Session;
+use flotilla::FlotillaError;
+use crate::output;
+use serde_json::json;
 
-pub fn exec() -> Result<(), String>
+pub fn exec(config: config::Config, username: Option<String>, endpoint: Option<String>, all: Option<bool>, forget: Option<bool>) -> Result<(), FlotillaError>
 {
-    if let Err(x) = config::Config::new().load_file().expect("Could not load config file").remove()
+    if all.unwrap_or(false)
     {
-        return Err(format!("Could not remove config file: {}", x));
+        Session::remove_all()
+            .map_err(|e| e.context("Could not remove sessions"))?;
+        eprintln!("Logged out of all sessions.");
+        output::emit(&json!({ "all": true }), || {});
+        return Ok(());
     }
-    if let Err(x) = Session::new().remove()
+
+    // Logging out must work even when the config file is gone
+    let config = match config.clone().load_file()
+    {
+        Ok(loaded) => loaded,
+        Err(_) => config,
+    }
+    .load_env()
+    .from_options(username, None, endpoint);
+
+    if forget.unwrap_or(false)
     {
-        return Err(format!("Could not remove session file: {}", x));
+        credentials::forget(&config.endpoint, &config.username)
+            .map_err(|e| e.context("Could not remove stored password"))?;
+        eprintln!("Removed the stored password for {}.", config.username);
     }
-    eprintln!("Logged out.");
+
+    let logged_out = match Session::load_for(&config).remove()
+    {
+        Ok(_) => {
+            eprintln!("Logged out {} at {}.", config.username, config.endpoint);
+            true
+        },
+        Err(FlotillaError::NotFound(_)) => {
+            eprintln!("Not logged in as {} at {}.", config.username, config.endpoint);
+            false
+        },
+        Err(e) => return Err(e.context("Could not remove session")),
+    };
+    output::emit(&json!({
+        "all": false,
+        "user": config.username,
+        "endpoint": config.endpoint,
+        "logged_out": logged_out,
+        "forgot_password": forget.unwrap_or(false),
+    }), || {});
     Ok(())
 }
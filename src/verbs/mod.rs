@@ -1,7 +1,15 @@
+use flotilla::FlotillaError;
+use flotilla::session::Session;
+use similar::{TextDiff, ChangeTag};
+
 pub mod verify;
+pub mod check;
 pub mod setup;
 pub mod login;
 pub mod logout;
+pub mod whoami;
+pub mod sessions;
+pub mod cache;
 pub mod list;
 pub mod get;
 pub mod fetch;
@@ -11,3 +19,67 @@ pub mod delete;
 pub mod update;
 pub mod create;
 pub mod edit;
+
+fn refreshable(session: &Session) -> bool
+{
+    !matches!(session.refresh_token.trim_matches('"'), "" | "null")
+}
+
+/// The --output schema for a session, which never includes its tokens
+pub fn session_json(session: &Session) -> serde_json::Value
+{
+    serde_json::json!({
+        "user": session.user_id,
+        "endpoint": session.endpoint,
+        "expires": session.expiration_unix,
+        "expires_at": chrono::DateTime::from_timestamp(session.expiration_unix, 0).map(|t| t.to_rfc3339()),
+        "expired": session.expired(),
+        "refreshable": refreshable(session),
+    })
+}
+
+/// When a session expires, in local time, or that it already has
+pub fn describe_expiry(session: &Session) -> String
+{
+    if !session.expired()
+    {
+        let until = chrono::DateTime::from_timestamp(session.expiration_unix, 0)
+            .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_default();
+        return format!("valid until {}", until);
+    }
+    match refreshable(session)
+    {
+        false => "expired".to_string(),
+        true => "expired (will be refreshed on next use)".to_string(),
+    }
+}
+
+/// Prints a line diff of two JSON values
+pub fn print_diff(old: &serde_json::Value, new: &serde_json::Value)
+{
+    for change in TextDiff::from_lines(
+            &serde_json::to_string_pretty(old).unwrap(),
+            &serde_json::to_string_pretty(new).unwrap()
+        )
+        .iter_all_changes() {
+        let sign = match change.tag() {
+            ChangeTag::Delete => "-",
+            ChangeTag::Insert => "+",
+            ChangeTag::Equal => " ",
+        };
+        print!("{}{}", sign, change);
+    }
+}
+
+/// Asks the user to type "yes" before continuing. Returns true if they did
+pub fn confirm(question: &str) -> Result<bool, FlotillaError>
+{
+    // On stderr, so stdout stays clean for --output
+    eprintln!();
+    eprintln!("{} (type yes to confirm)", question);
+    eprintln!();
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input).map_err(|e| FlotillaError::Io(format!("Could not read input: {}", e)))?;
+    Ok(input.trim().eq_ignore_ascii_case("yes"))
+}
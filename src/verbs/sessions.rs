@@ -0,0 +1,38 @@
+use flotilla::config::Config;
+use flotilla::session::Session;
+use flotilla::FlotillaError;
+use super::{describe_expiry, session_json};
+use crate::output;
+
+pub fn exec(config: Config) -> Result<(), FlotillaError>
+{
+    // The current session is only marked if there is a config to tell which one it is
+    let current = config
+        .load_all(None,None,None)
+        .ok();
+
+    let sessions = Session::load_stored();
+    let is_current = |session: &Session| match &current
+    {
+        Some(c) => c.username == session.user_id && c.endpoint == session.endpoint,
+        None => false,
+    };
+
+    let report: Vec<serde_json::Value> = sessions.iter().map(|session| {
+        let mut json = session_json(session);
+        json["current"] = is_current(session).into();
+        json
+    }).collect();
+    output::emit(&report, || {
+        if sessions.is_empty()
+        {
+            eprintln!("No stored sessions.");
+        }
+        for session in sessions.iter()
+        {
+            let marker = if is_current(session) { "*" } else { " " };
+            println!("{} {}\t{}\t{}", marker, session.user_id, session.endpoint, describe_expiry(session));
+        }
+    });
+    Ok(())
+}
@@ -1,21 +1,67 @@
-use crate::config::Config;
+use flotilla::config::Config;
+use flotilla::credentials;
+use flotilla::FlotillaError;
+use crate::output;
+use std::io::Write;
 
-pub fn exec(username: Option<String>, password: Option<String>, endpoint: Option<String>) -> Result<(), String> {
-    if let Ok(config) = Config::new().load_file(){
+pub fn exec(config: Config, username: Option<String>, password: Option<String>, endpoint: Option<String>) -> Result<(), FlotillaError> {
+    if std::path::Path::new(&config.location()).exists(){
         eprintln!("Overwriting existing config file.");
-        let backup = format!("{}.bak", config.location());
-        match std::fs::copy(config.location(), backup)
-        {
-            Ok(_) => {},
-            Err(x) => return Err(format!("Could not backup config file: {}", x).to_string()),
-        }
+        backup(&config.location())?;
     }
-    let cfg = Config::new().load_env().from_options(username, password, endpoint);
-    match cfg.save_to_default()
+    let mut cfg = config.load_env().from_options(username, password, endpoint);
+    if cfg.username.is_empty()
     {
-        Ok(_) => {},
-        Err(x) => return Err(format!("Could not save config file: {}", x).to_string()),
-    } 
+        cfg.username = prompt("Username: ")?;
+    }
+    if cfg.password.is_empty()
+    {
+        cfg.password = rpassword::prompt_password("Password: ")
+            .map_err(|e| FlotillaError::Io(format!("Could not read the password: {}", e)))?;
+    }
+    if cfg.username.is_empty() || cfg.password.is_empty()
+    {
+        return Err(FlotillaError::Usage("A username and password are required".to_string()));
+    }
+
+    eprintln!("Your password will be encrypted with a passphrase of your choosing.");
+    credentials::store(&cfg.endpoint, &cfg.username, &cfg.password, &credentials::passphrase(true)?)
+        .map_err(|e| e.context("Could not store the password"))?;
+    eprintln!("Password stored in {}", credentials::location().display());
+
+    cfg.save_to_default().map_err(|e| e.context("Could not save config file"))?;
     eprintln!("Config file saved to {}", cfg.location());
+    output::emit(&serde_json::json!({
+        "config": cfg.location(),
+        "credentials": credentials::location(),
+        "user": cfg.username,
+        "endpoint": cfg.endpoint,
+        "profile": cfg.profile,
+    }), || {});
     Ok(())
 }
+
+/// Copies the config file aside, leaving out any plaintext password from older versions, since
+/// setup is about to move it to the credential store
+fn backup(location: &str) -> Result<(), FlotillaError>
+{
+    let backup = format!("{}.bak", location);
+    let error = |x: String| FlotillaError::ConfigIo(format!("Could not backup config file: {}", x));
+    let mut contents = ini::Ini::load_from_file(location).map_err(|e| error(e.to_string()))?;
+    match contents.delete_from(Some("user"), "password")
+    {
+        Some(_) => contents.write_to_file(&backup).map_err(|e| error(e.to_string())),
+        None => std::fs::copy(location, &backup).map(|_| ()).map_err(|e| error(e.to_string())),
+    }
+}
+
+fn prompt(question: &str) -> Result<String, FlotillaError>
+{
+    eprint!("{}", question);
+    std::io::stderr().flush().ok();
+    let mut answer = String::new();
+    std::io::stdin()
+        .read_line(&mut answer)
+        .map_err(|e| FlotillaError::Io(format!("Could not read from stdin: {}", e)))?;
+    Ok(answer.trim().to_string())
+}
@@ -0,0 +1,45 @@
+use flotilla::api::IdType;
+use flotilla::api::blocking::Flotilla;
+use flotilla::config::Config;
+use flotilla::session::Session;
+use flotilla::FlotillaError;
+use super::print_diff;
+use crate::output;
+use serde_json::json;
+
+pub fn exec(config: Config, id: String, file: std::path::PathBuf) -> Result<(), FlotillaError>
+{
+    if file.extension().and_then(|x| x.to_str()) != Some("seria")
+    {
+        return Err(FlotillaError::Usage(format!("{} - Not a .seria file", file.display())));
+    }
+    let file_name = file
+        .file_name()
+        .and_then(|x| x.to_str())
+        .ok_or(FlotillaError::Usage(format!("{} - Invalid file name", file.display())))?;
+    let contents = std::fs::read_to_string(&file)
+        .map_err(|e| FlotillaError::Io(format!("{} - Could not read file: {}", file.display(), e)))?;
+
+    let config = config
+        .load_all(None,None,None)?;
+
+    let session = Session::load_for(&config);
+
+    let flt = Flotilla::new(config, session);
+    let id = flt.resolve_id(&id, Some(IdType::Ship))?;
+    let before = flt.get_current_ship(&id).map_err(|e| e.context(format!("Could not find ship {}", id)))?;
+
+    eprintln!("Uploading {} as a new revision of '{}'...", file.display(), before.name);
+    let after = flt.update_ship_file(&id, file_name, contents)
+        .map_err(|e| e.context(format!("Could not update ship {}", id)))?;
+
+    if after.id != before.id || after.short_id != before.short_id
+    {
+        eprintln!("Warning: the server changed the ship id from {} ({}) to {} ({})",
+                  before.id, before.short_id, after.id, after.short_id);
+    }
+
+    output::emit(&json!({ "before": before, "after": after }), || print_diff(&json!(before), &json!(after)));
+    eprintln!("Ship updated.");
+    Ok(())
+}
@@ -0,0 +1,92 @@
+use flotilla::api;
+use flotilla::config::Config;
+use flotilla::session::Session;
+use flotilla::FlotillaError;
+use crate::output;
+use indicatif::{ProgressBar, ProgressStyle};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub fn exec(config: Config, files: Vec<PathBuf>) -> Result<(), FlotillaError>
+{
+    if files.is_empty()
+    {
+        return Err(FlotillaError::Usage("No files given. Please provide one or more .seria files to upload.".to_string()));
+    }
+
+    let config = config
+        .load_all(None,None,None)?;
+
+    let session = Session::load_for(&config);
+
+    let flt = api::blocking::Flotilla::new(config, session);
+    let multi = output::progress();
+
+    let bars: Vec<ProgressBar> = files.iter().map(|file| {
+        let pb = multi.add(ProgressBar::new(3));
+        pb.set_style(
+            ProgressStyle::with_template(
+                "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {msg}"
+                )
+            .unwrap()
+            .progress_chars("#>-"),
+            );
+        pb.enable_steady_tick( Duration::from_millis(100) );
+        pb.set_message(format!("{} - Waiting ... ", file.display()));
+        pb
+    }).collect();
+
+    let mut ships = Vec::new();
+    let mut errors = Vec::new();
+    for (file, pb) in files.iter().zip(bars.iter())
+    {
+        match upload_one(&flt, file, pb)
+        {
+            Ok(ship) => {
+                pb.finish_with_message(format!("{} - Uploaded as {}", file.display(), ship.short_id));
+                ships.push(ship);
+            },
+            Err(e) => {
+                pb.abandon_with_message(e.to_string());
+                errors.push(e);
+            }
+        }
+    }
+
+    let uploaded: Vec<serde_json::Value> = ships.iter().map(|s| serde_json::json!({
+        "id": s.id,
+        "shortId": s.short_id,
+        "downloadUrl": s.download_url,
+    })).collect();
+    output::emit(&uploaded, || {
+        if !uploaded.is_empty()
+        {
+            println!("{}", serde_json::to_string_pretty(&uploaded).expect("Application Error: Could not serialize ship data. Please file a bug!"));
+        }
+    });
+
+    FlotillaError::from_many(errors)
+}
+
+fn upload_one(flt: &api::blocking::Flotilla, file: &Path, pb: &ProgressBar) -> Result<api::Ship, FlotillaError>
+{
+    pb.inc(1);
+    pb.set_message(format!("{} - Reading", file.display()));
+    if file.extension().and_then(|x| x.to_str()) != Some("seria")
+    {
+        return Err(FlotillaError::Usage(format!("{} - Not a .seria file", file.display())));
+    }
+    let file_name = file
+        .file_name()
+        .and_then(|x| x.to_str())
+        .ok_or(FlotillaError::Usage(format!("{} - Invalid file name", file.display())))?;
+    let contents = std::fs::read_to_string(file)
+        .map_err(|e| FlotillaError::Io(format!("{} - Could not read file: {}", file.display(), e)))?;
+
+    pb.inc(1);
+    pb.set_message(format!("{} - Uploading", file.display()));
+    let ship = flt.upload_ship(file_name, contents)
+        .map_err(|e| e.context(format!("{} - Upload failed", file.display())))?;
+    pb.inc(1);
+    Ok(ship)
+}
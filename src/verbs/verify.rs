@@ -1,6 +1,34 @@
-pub fn exec(file: std::path::PathBuf) -> Result<(), String>
+use flotilla::seria;
+use flotilla::FlotillaError;
+use crate::output;
+
+pub fn exec(file: std::path::PathBuf) -> Result<(), FlotillaError>
 {
-    Err(format!("Not implemented yet: verify {}", file.display()))
-}
+    let contents = std::fs::read_to_string(&file)
+        .map_err(|e| FlotillaError::Io(format!("Could not read {}: {}", file.display(), e)))?;
 
+    let problems = seria::validate(&contents);
+    let errors = problems.iter().filter(|p| p.is_error()).count();
+    output::emit(&serde_json::json!({
+        "file": file,
+        "valid": errors == 0,
+        "problems": problems,
+    }), || {
+        for problem in problems.iter()
+        {
+            eprintln!("{}:{}", file.display(), problem);
+        }
+    });
 
+    match errors
+    {
+        0 => {
+            if output::is_human()
+            {
+                eprintln!("{} is valid.", file.display());
+            }
+            Ok(())
+        },
+        n => Err(FlotillaError::Validation(format!("{} error(s) found in {}", n, file.display()))),
+    }
+}
@@ -0,0 +1,35 @@
+use flotilla::config::Config;
+use flotilla::session::Session;
+use flotilla::FlotillaError;
+use super::{describe_expiry, session_json};
+use crate::output;
+use serde_json::json;
+
+pub fn exec(config: Config) -> Result<(), FlotillaError>
+{
+    let config = config
+        .load_all(None,None,None)?;
+
+    if config.username.is_empty()
+    {
+        return Err(FlotillaError::AuthExpired);
+    }
+
+    let session = Session::load_for(&config);
+    let report = json!({
+        "user": config.username,
+        "endpoint": config.endpoint,
+        "profile": config.profile,
+        "session": session_json(&session),
+    });
+    output::emit(&report, || {
+        println!("{}", config.username);
+        eprintln!("Endpoint: {}", config.endpoint);
+        if let Some(profile) = &config.profile
+        {
+            eprintln!("Profile:  {}", profile);
+        }
+        eprintln!("Session:  {}", describe_expiry(&session));
+    });
+    Ok(())
+}